@@ -0,0 +1,294 @@
+//! Runs a ROM without a window and dumps the final machine state, for CI and
+//! batch jobs on machines with no display.
+
+use chip8::{Chip8Error, Cpu, Movie, Quirks};
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+
+const USAGE: &str = "Usage: chip8-headless <rom file> [options]
+
+Options:
+    --frames <n>    run for n 60Hz frames (default 600)
+    --cycles <n>    run for n instructions instead of a number of frames
+    --ipf <n>       instructions executed per frame (default 10)
+    --keys <file>   replay a movie, or a key timeline with one
+                    \"<frame> press|release <key>\" per line. A seed, speed and
+                    quirks recorded in the movie are used unless given here
+    --quirks <name> interpreter behaviour: default, vip, chip48 or schip
+    --seed <n>      seed for the random number generator (default 0)
+    --json          print the final state as JSON";
+
+const DEFAULT_FRAMES: u64 = 600;
+
+enum Limit {
+    Frames(u64),
+    Cycles(u64),
+}
+
+struct Config {
+    rom: String,
+    limit: Limit,
+    ipf: u32,
+    timeline: Movie,
+    quirks: Quirks,
+    seed: u64,
+    json: bool,
+}
+
+/// Final state of a run
+struct Report {
+    frames: u64,
+    cycles: u64,
+    error: Option<Chip8Error>,
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("chip8-headless: {}\n{}", msg, USAGE);
+    std::process::exit(1);
+}
+
+fn parse_number(flag: &str, value: Option<&String>) -> u64 {
+    match value.map(|v| v.parse::<u64>()) {
+        Some(Ok(n)) => n,
+        _ => usage_error(&format!("{} expects a non-negative integer", flag)),
+    }
+}
+
+fn process_args(args: &[String]) -> Config {
+    let mut rom = None;
+    let mut limit = Limit::Frames(DEFAULT_FRAMES);
+    let mut ipf = None;
+    let mut timeline = Movie::new();
+    let mut quirks = None;
+    let mut seed = None;
+    let mut json = false;
+
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => limit = Limit::Frames(parse_number(arg, args.next())),
+            "--cycles" => limit = Limit::Cycles(parse_number(arg, args.next())),
+            "--ipf" => {
+                ipf = match u32::try_from(parse_number(arg, args.next())) {
+                    // Zero would never finish a frame, so a frame limit would spin forever
+                    Ok(0) => usage_error("--ipf must be at least 1"),
+                    Ok(n) => Some(n),
+                    Err(_) => usage_error("--ipf is too large"),
+                };
+            },
+            "--seed" => seed = Some(parse_number(arg, args.next())),
+            "--keys" => {
+                let fname = match args.next() {
+                    Some(fname) => fname,
+                    None => usage_error("--keys expects a file name"),
+                };
+                let text = match fs::read_to_string(fname) {
+                    Ok(text) => text,
+                    Err(err) => usage_error(&format!("cannot read \"{}\": {}", fname, err)),
+                };
+                timeline = match Movie::parse(&text) {
+                    Ok(timeline) => timeline,
+                    Err(err) => usage_error(&format!("{}: {}", fname, err)),
+                };
+            },
+            "--quirks" => {
+                quirks = match args.next().and_then(|name| Quirks::preset(name)) {
+                    Some(quirks) => Some(quirks),
+                    None => usage_error(&format!("--quirks expects one of: {}", Quirks::PRESETS.join(", "))),
+                };
+            },
+            "--json" => json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ if rom.is_none() => rom = Some(arg.to_string()),
+            _ => usage_error("only one ROM file may be given"),
+        }
+    }
+
+    let ipf = ipf.or(timeline.ipf).unwrap_or(Cpu::DEFAULT_INSTRUCTIONS_PER_FRAME);
+    if ipf == 0 {
+        usage_error("the movie's ipf must be at least 1");
+    }
+    let quirks = quirks.or(timeline.quirks).unwrap_or_default();
+    let seed = seed.or(timeline.seed).unwrap_or(0);
+    match rom {
+        Some(rom) => Config { rom, limit, ipf, timeline, quirks, seed, json },
+        None => usage_error("no ROM file given"),
+    }
+}
+
+fn run(cpu: &mut Cpu, cfg: &Config) -> Report {
+    let mut report = Report { frames: 0, cycles: 0, error: None };
+
+    loop {
+        match cfg.limit {
+            Limit::Frames(frames) if report.frames >= frames => break,
+            Limit::Cycles(cycles) if report.cycles >= cycles => break,
+            _ => (),
+        }
+
+        cfg.timeline.apply(report.frames, cpu);
+
+        // Not using run_frame, so that a cycle limit can stop mid-frame
+        for _ in 0..cpu.instructions_per_frame() {
+            if let Limit::Cycles(cycles) = cfg.limit {
+                if report.cycles >= cycles {
+                    break;
+                }
+            }
+            if let Err(err) = cpu.advance_state() {
+                report.error = Some(err);
+                return report;
+            }
+            report.cycles += 1;
+        }
+        cpu.decrement_timers();
+        report.frames += 1;
+    }
+    report
+}
+
+/// 64-bit FNV-1a hash of the framebuffer, stable across platforms and builds
+fn framebuffer_hash(cpu: &Cpu) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for pixel in cpu.graphics().iter().flatten() {
+        hash ^= u64::from(*pixel != 0);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+fn framebuffer_rows(cpu: &Cpu) -> Vec<String> {
+    cpu.graphics().iter()
+        .map(|row| row.iter().map(|pixel| if *pixel != 0 { '#' } else { '.' }).collect())
+        .collect()
+}
+
+fn print_text(cpu: &Cpu, report: &Report) {
+    println!("frames: {}", report.frames);
+    println!("cycles: {}", report.cycles);
+    println!("pc: {:#06x}", cpu.pc());
+    println!("i: {:#06x}", cpu.index());
+    let v: Vec<String> = cpu.registers().iter().map(|r| format!("{:02x}", r)).collect();
+    println!("v: {}", v.join(" "));
+    let stack: Vec<String> = cpu.stack().iter().map(|addr| format!("{:#06x}", addr)).collect();
+    println!("stack: [{}]", stack.join(", "));
+    println!("delay_timer: {}", cpu.delay_timer());
+    println!("sound_timer: {}", cpu.sound_timer());
+    println!("hash: {:016x}", framebuffer_hash(cpu));
+    if let Some(err) = &report.error {
+        println!("error: {}", err);
+    }
+    for row in framebuffer_rows(cpu) {
+        println!("{}", row);
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn print_json(cpu: &Cpu, report: &Report) {
+    let join = |items: Vec<String>| items.join(",");
+    let v = join(cpu.registers().iter().map(|r| r.to_string()).collect());
+    let stack = join(cpu.stack().iter().map(|addr| addr.to_string()).collect());
+    let rows = join(framebuffer_rows(cpu).iter().map(|row| json_string(row)).collect());
+    let error = match &report.error {
+        Some(err) => json_string(&err.to_string()),
+        None => String::from("null"),
+    };
+    println!("{{\"frames\":{},\"cycles\":{},\"pc\":{},\"index\":{},\"v\":[{}],\"stack\":[{}],\
+              \"delay_timer\":{},\"sound_timer\":{},\"hash\":\"{:016x}\",\"error\":{},\"framebuffer\":[{}]}}",
+             report.frames, report.cycles, cpu.pc(), cpu.index(), v, stack,
+             cpu.delay_timer(), cpu.sound_timer(), framebuffer_hash(cpu), error, rows);
+}
+
+#[cfg(not(tarpaulin_include))]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let config = process_args(&args);
+
+    let mut cpu = Cpu::with_quirks(config.quirks);
+    cpu.set_seed(config.seed);
+    cpu.set_instructions_per_frame(config.ipf);
+    let rom = match fs::read(&config.rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("chip8-headless: error loading ROM file: \"{}\": {}", config.rom, err);
+            std::process::exit(1);
+        },
+    };
+    if let Err(err) = cpu.load_rom_bytes(&rom) {
+        eprintln!("chip8-headless: error loading ROM file: \"{}\": {}", config.rom, err);
+        std::process::exit(1);
+    }
+    if let Err(err) = config.timeline.check_rom(&rom) {
+        eprintln!("chip8-headless: {}", err);
+        std::process::exit(1);
+    }
+
+    let report = run(&mut cpu, &config);
+    if config.json {
+        print_json(&cpu, &report);
+    } else {
+        print_text(&cpu, &report);
+    }
+    if report.error.is_some() {
+        std::process::exit(2);
+    }
+}
+
+#[cfg(test)]
+mod headless_tests {
+    use super::*;
+    use chip8::{InputEvent, Key};
+
+    #[test]
+    fn test_parse_timeline() {
+        let text = "# start the game\n30 release 5\n\n10 press 5\n12 press f\n";
+        let timeline = Movie::parse(text).unwrap();
+        assert_eq!(timeline.events, vec![
+            InputEvent { frame: 10, key: Key::Num5, pressed: true },
+            InputEvent { frame: 12, key: Key::F, pressed: true },
+            InputEvent { frame: 30, key: Key::Num5, pressed: false },
+        ]);
+        assert!(Movie::parse("10 press 10").is_err()); // no key 0x10
+        assert!(Movie::parse("10 hold 1").is_err());
+        assert!(Movie::parse("press 1").is_err());
+    }
+
+    #[test]
+    fn test_run_cycles() {
+        let base_path = env!("CARGO_MANIFEST_DIR");
+        let config = Config {
+            rom: format!("{}/{}", base_path, "test_opcode.ch8"),
+            limit: Limit::Cycles(25),
+            ipf: 10,
+            timeline: Movie::new(),
+            quirks: Quirks::default(),
+            seed: 0,
+            json: false,
+        };
+        let mut cpu = Cpu::new();
+        cpu.set_instructions_per_frame(config.ipf);
+        cpu.load_rom(&config.rom).unwrap();
+        let report = run(&mut cpu, &config);
+        assert!(report.error.is_none());
+        assert_eq!(report.cycles, 25);
+        assert_eq!(report.frames, 3);
+    }
+}
@@ -1,32 +1,41 @@
-extern crate sdl2;
 use std::fs::File;
-use std::io::prelude::*;
-use rand::thread_rng;
-use rand::Rng;
-use crate::Config;
-
-use sdl2::video::Window;
-use sdl2::render::Canvas;
-use sdl2::rect::Rect;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use std::io::Read;
+use crate::display::{Display, Framebuffer};
+use crate::error::Chip8Error;
+use crate::instruction::{decode, Instruction};
+use crate::keypad::Key;
+use crate::memory::Memory;
+use crate::platform::Platform;
+use crate::quirks::{IndexIncrement, Quirks};
+use crate::rng::{RandomSource, SeededRng};
+use crate::stack::{Stack, StackError};
+use crate::state::MachineState;
 
 #[derive(Debug)]
 pub struct Cpu {
-    memory: [u8; 0xFFF],
-    graphics: [[u8; 64]; 32],
+    memory: Memory,
+    graphics: Framebuffer,
     v: [u8; 16],
     index: u16,
     pc: u16,
-    stack: Vec<u16>,
+    stack: Stack,
     sound_timer: u8,
     delay_timer: u8,
-    current_op: (u8,u8,u8,u8),
     key_pressed: [i32; 16],
     redraw_gfx: bool,
+    platform: Platform,
+    quirks: Quirks,
+    vblank: bool, // Set at each 60Hz tick, cleared when a sprite is drawn
+    rng: Box<dyn RandomSource>,
+    instructions_per_frame: u32,
 }
-// TODO: Remove this directive after writing main fn!
-#[allow(dead_code)]
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
+}
+
 impl Cpu {
     pub const FONT: [u8; 80] = [0xF0, 0x90, 0x90, 0x90, 0xF0,
                                 0x20, 0x60, 0x20, 0x20, 0x70,
@@ -47,340 +56,490 @@ impl Cpu {
     
     pub const GFX_HEIGHT: usize = 32;
     pub const GFX_WIDTH: usize  = 64;
+    pub const FRAME_RATE: u32 = 60; // Timers tick and the screen refreshes at 60Hz
+    pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 10;
 
     // Public functions
     pub fn new() -> Cpu {
-        let mut mem = [0u8; 0xFFF];
+        Cpu::with_quirks(Quirks::default())
+    }
+
+    /// Creates a processor that emulates the given interpreter behaviour
+    pub fn with_quirks(quirks: Quirks) -> Cpu {
+        Cpu::with_platform(Platform::default(), quirks).expect("the default platform is valid")
+    }
+
+    /// Creates a processor for the given hardware and interpreter behaviour.
+    /// Fails if the platform has too little or too much memory, or its load
+    /// address is outside memory
+    pub fn with_platform(platform: Platform, quirks: Quirks) -> Result<Cpu, Chip8Error> {
+        platform.check()?;
+        let mut mem = Memory::new(platform.memory_size, platform.address_policy);
         mem[0..80].copy_from_slice(&Cpu::FONT);
 
-        Cpu {
+        Ok(Cpu {
             memory: mem,
             graphics: [[0;64];32],
             v: [0; 16],
             index: 0,
-            pc: 0x200,
-            stack: Vec::<u16>::new(),
+            pc: platform.load_address,
+            stack: Stack::new(platform.stack_depth),
             sound_timer: 0,
             delay_timer: 0,
-            current_op: (0,0,0,0),
             key_pressed: [0; 16],
             redraw_gfx: false,
-        }
+            platform,
+            quirks,
+            vblank: false,
+            rng: Box::new(SeededRng::from_entropy()),
+            instructions_per_frame: Cpu::DEFAULT_INSTRUCTIONS_PER_FRAME,
+        })
     }
     pub fn reset(&mut self) {
-        self.memory = [0; 0xFFF];
+        self.memory.clear();
         self.graphics = [[0;64];32];
         self.v = [0;16];
         self.index = 0;
-        self.pc = 0x200;
-        self.stack = Vec::<u16>::new();
+        self.pc = self.platform.load_address;
+        self.stack.clear();
         self.sound_timer = 0;
         self.delay_timer = 0;
-        self.current_op = (0,0,0,0);
         self.key_pressed = [0; 16];
         self.redraw_gfx = false;
+        self.vblank = false;
 
         self.memory[0..80].copy_from_slice(&Cpu::FONT);
     }
 
     pub fn decrement_timers(&mut self) {
+        self.vblank = true;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
         }
-        if self.sound_timer > 0 {
-            self.play_sound();
+    }
+
+    /// Resets the machine and loads the ROM file at `filename`
+    pub fn load_rom(&mut self, filename: &str) -> Result<(), Chip8Error> {
+        let file = File::open(filename)?;
+        self.load_rom_reader(file)
+    }
+
+    /// Resets the machine and loads a ROM read to the end of `reader`. Stops
+    /// reading as soon as the ROM is known not to fit, so the size reported
+    /// by `RomTooLarge` is then one byte over the maximum
+    pub fn load_rom_reader<R: Read>(&mut self, reader: R) -> Result<(), Chip8Error> {
+        let max = self.max_rom_size();
+        let mut rom = Vec::new();
+        reader.take(max as u64 + 1).read_to_end(&mut rom)?;
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max });
         }
+        self.load_rom_bytes(&rom)
     }
 
-    #[allow(clippy::unused_io_amount)]
-    pub fn load_rom(&mut self, filename: &str) -> Result<(), std::io::Error> {
+    /// Resets the machine and copies `rom` to the platform's load address
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let start = self.platform.load_address as usize;
+        let max = self.max_rom_size();
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max });
+        }
         self.reset();
-        let mut file = File::open(filename)?;
-        let filelen = file.metadata().unwrap().len();
-        if filelen > 0xFFF - 0x200 {
-            panic!("ROM file is greater than 3.5K bytes! Exiting.");
+        self.memory[start..start + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    pub fn press_button(&mut self, key: Key) {
+        self.key_pressed[key.index()] = 1;
+    }
+
+    pub fn release_button(&mut self, key: Key) {
+        self.key_pressed[key.index()] = 0;
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.key_pressed[key.index()] != 0
+    }
+
+    /// Executes a single instruction. On error the machine is left as it was
+    /// when the fault occurred, so the host can inspect it or carry on
+    pub fn advance_state(&mut self) -> Result<(), Chip8Error> {
+        self.execute_next_op()
+    }
+
+    /// Emulates one 60Hz frame: executes `instructions_per_frame` instructions,
+    /// then ticks the timers once. The caller is responsible for calling this
+    /// `FRAME_RATE` times a second
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        for _ in 0..self.instructions_per_frame {
+            self.advance_state()?;
         }
-        file.read( &mut self.memory[0x200..0xFFF])?;
-        
+        self.decrement_timers();
         Ok(())
     }
 
-    pub fn press_button(&mut self, key: usize) {
-        self.key_pressed[key] = 1;
+    /// Executes a single instruction, for stepping through a program by
+    /// hand. A draw waiting for the next frame under the `display_wait`
+    /// quirk is treated as having waited, so every step makes progress
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if let Ok(Instruction::Draw { .. }) = self.peek_instruction() {
+            self.vblank = true;
+        }
+        self.advance_state()
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /// Sets the emulation speed. Most ROMs expect somewhere between 7 and 15
+    pub fn set_instructions_per_frame(&mut self, count: u32) {
+        self.instructions_per_frame = count;
+    }
+
+    /// Decodes the instruction at the program counter without executing it
+    pub fn peek_instruction(&self) -> Result<Instruction, Chip8Error> {
+        decode(self.opcode_at(self.pc)?)
+            .map_err(|err| Chip8Error::UnknownOpcode { opcode: err.opcode, address: self.pc })
+    }
+
+    /// Executes a decoded instruction. The program counter is expected to
+    /// already point past it, as it does after `advance_state` fetches one
+    pub fn execute(&mut self, instruction: &Instruction) -> Result<(), Chip8Error> {
+        match *instruction {
+            Instruction::ClearScreen => self.clear_screen(),
+            Instruction::Return => self.op_00ee()?,
+            Instruction::Jump { nnn } => self.op_1nnn(nnn),
+            Instruction::Call { nnn } => self.op_2nnn(nnn)?,
+            Instruction::SkipEqImm { x, nn } => self.op_3xnn(x as usize, nn),
+            Instruction::SkipNeImm { x, nn } => self.op_4xnn(x as usize, nn),
+            Instruction::SkipEqReg { x, y } => self.op_5xy0(x as usize, y as usize),
+            Instruction::LoadImm { x, nn } => self.op_6xnn(x as usize, nn),
+            Instruction::AddImm { x, nn } => self.op_7xnn(x as usize, nn),
+            Instruction::Move { x, y } => self.op_8xy0(x as usize, y as usize),
+            Instruction::Or { x, y } => self.op_8xy1(x as usize, y as usize),
+            Instruction::And { x, y } => self.op_8xy2(x as usize, y as usize),
+            Instruction::Xor { x, y } => self.op_8xy3(x as usize, y as usize),
+            Instruction::Add { x, y } => self.op_8xy4(x as usize, y as usize),
+            Instruction::Sub { x, y } => self.op_8xy5(x as usize, y as usize),
+            Instruction::ShiftRight { x, y } => self.op_8xy6(x as usize, y as usize),
+            Instruction::SubReverse { x, y } => self.op_8xy7(x as usize, y as usize),
+            Instruction::ShiftLeft { x, y } => self.op_8xye(x as usize, y as usize),
+            Instruction::SkipNeReg { x, y } => self.op_9xy0(x as usize, y as usize),
+            Instruction::LoadIndex { nnn } => self.op_annn(nnn),
+            Instruction::JumpOffset { x, nnn } => self.op_bnnn(x as usize, nnn),
+            Instruction::Random { x, nn } => self.op_cxnn(x as usize, nn),
+            Instruction::Draw { x, y, n } => self.op_dxyn(x as usize, y as usize, n as usize)?,
+            Instruction::SkipKeyPressed { x } => self.op_ex9e(x as usize),
+            Instruction::SkipKeyNotPressed { x } => self.op_exa1(x as usize),
+            Instruction::LoadDelay { x } => self.op_fx07(x as usize),
+            Instruction::WaitKey { x } => self.op_fx0a(x as usize),
+            Instruction::SetDelay { x } => self.op_fx15(x as usize),
+            Instruction::SetSound { x } => self.op_fx18(x as usize),
+            Instruction::AddIndex { x } => self.op_fx1e(x as usize),
+            Instruction::LoadFont { x } => self.op_fx29(x as usize),
+            Instruction::StoreBcd { x } => self.op_fx33(x as usize)?,
+            Instruction::StoreRegisters { x } => self.op_fx55(x as usize)?,
+            Instruction::LoadRegisters { x } => self.op_fx65(x as usize)?,
+        }
+        Ok(())
     }
 
-    pub fn release_button(&mut self, key: usize) {
-        self.key_pressed[key] = 0;
+    /// Reseeds the random number generator used by CXNN. Two machines given
+    /// the same seed, ROM and input produce identical runs
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Box::new(SeededRng::new(seed));
     }
 
-    pub fn advance_state(&mut self) {
-        self.execute_next_op();
+    /// Replaces the random number generator used by CXNN
+    pub fn set_random_source(&mut self, source: Box<dyn RandomSource>) {
+        self.rng = source;
     }
 
-    /// Updates the SDL canvas with the contents of the processor's graphics memory
-    /// Assumes an initialized Config object and an initialized SDL context with
-    /// a canvas
-    pub fn update_graphics(&mut self, cfg: &Config, canvas: &mut Canvas<Window>) {
-        if self.redraw_gfx {
-            canvas.set_draw_color(cfg.white);
-            canvas.clear();
-            for x in 0..Cpu::GFX_WIDTH {
-                for y in 0..Cpu::GFX_HEIGHT {
-                    match self.graphics[y][x] {
-                        0 => { canvas.set_draw_color(cfg.white); },
-                        _ => { canvas.set_draw_color(cfg.black); }
-                    }
-                    let x = x as u32 * cfg.scale;
-                    let y = y as u32 * cfg.scale;
-                    match canvas.fill_rect(Rect::new(x as i32, y as i32, cfg.scale, cfg.scale)) {
-                         Ok(()) => {},
-                         Err(err) => { println!("Error drawing rect: {}",err); },
-                    }
-                }
-            }
-            canvas.present();
-            self.redraw_gfx = false;
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // Accessors for embedding front ends and tools
+
+    /// The general purpose registers V0 through VF
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    /// The index register I
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// The program counter, i.e. the address of the next instruction
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Return addresses of the subroutines currently being executed,
+    /// innermost last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    /// Maximum number of nested subroutine calls
+    pub fn stack_depth(&self) -> usize {
+        self.stack.depth()
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The framebuffer, indexed as `graphics()[y][x]`. Non-zero pixels are set
+    pub fn graphics(&self) -> &Framebuffer {
+        &self.graphics
+    }
+
+    /// True if the framebuffer changed since it was last drawn
+    pub fn needs_redraw(&self) -> bool {
+        self.redraw_gfx
+    }
+
+    /// Clears the dirty flag, for front ends that draw `graphics()` themselves
+    pub fn mark_drawn(&mut self) {
+        self.redraw_gfx = false;
+    }
+
+    /// Which of the 16 keys are held down, indexed by key value
+    pub fn keypad(&self) -> [bool; 16] {
+        let mut keypad = [false; 16];
+        for (held, pressed) in keypad.iter_mut().zip(self.key_pressed.iter()) {
+            *held = *pressed != 0;
         }
+        keypad
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
     }
 
-    fn keycode_to_index(keycode: Keycode) -> usize {
-        match keycode {
-            Keycode::X    => { 0 },
-            Keycode::Num1 => { 1 },
-            Keycode::Num2 => { 2 },
-            Keycode::Num3 => { 3 },
-            Keycode::Q    => { 4 },
-            Keycode::W    => { 5 },
-            Keycode::E    => { 6 },
-            Keycode::A    => { 7 },
-            Keycode::S    => { 8 },
-            Keycode::D    => { 9 },
-            Keycode::Z    => { 10 },
-            Keycode::C    => { 11 },
-            Keycode::Num4 => { 12 },
-            Keycode::R    => { 13 },
-            Keycode::F    => { 14 },
-            Keycode::V    => { 15 },
-            _             => { 16 },
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// True while the buzzer should sound, i.e. the sound timer is non-zero.
+    /// Front ends poll this once per frame and play the tone themselves
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Captures the machine's state, e.g. for save states or to compare runs
+    pub fn snapshot(&self) -> MachineState {
+        MachineState {
+            memory: self.memory.to_vec(),
+            graphics: self.graphics.iter().flatten().copied().collect(),
+            v: self.v,
+            index: self.index,
+            pc: self.pc,
+            stack: self.stack.to_vec(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            keypad: self.keypad(),
+            vblank: self.vblank,
+            rng: self.rng.state(),
         }
     }
 
-    pub fn process_input(&mut self, e: Event) {
-        match e {
-            Event::KeyDown {keycode, ..} => {
-                let index = match keycode {
-                    Some(keycode) => Cpu::keycode_to_index(keycode),
-                    None => 16
-                };
-                if index < 16 { self.press_button(index); }
-            }
-            Event::KeyUp {keycode, ..} => {
-                let index = match keycode {
-                    Some(keycode) => Cpu::keycode_to_index(keycode),
-                    None => 16,
-                };
-                if index < 16 { self.release_button(index); }
-            }
-            _ => {}
+    /// Puts the machine back into a state taken with `snapshot`. The state
+    /// must come from a machine with the same memory size and at most the
+    /// same stack depth; if it doesn't, nothing is changed
+    pub fn restore(&mut self, state: &MachineState) -> Result<(), Chip8Error> {
+        if state.memory.len() != self.memory.len() {
+            return Err(Chip8Error::InvalidState(format!(
+                "memory is {} bytes, expected {}", state.memory.len(), self.memory.len())));
+        }
+        if state.graphics.len() != Cpu::GFX_WIDTH * Cpu::GFX_HEIGHT {
+            return Err(Chip8Error::InvalidState(format!(
+                "framebuffer is {} pixels, expected {}", state.graphics.len(), Cpu::GFX_WIDTH * Cpu::GFX_HEIGHT)));
+        }
+        if state.stack.len() > self.stack.depth() {
+            return Err(Chip8Error::InvalidState(format!(
+                "{} return addresses on the stack, maximum is {}", state.stack.len(), self.stack.depth())));
+        }
+
+        self.memory.copy_from_slice(&state.memory);
+        for (row, pixels) in self.graphics.iter_mut().zip(state.graphics.chunks(Cpu::GFX_WIDTH)) {
+            row.copy_from_slice(pixels);
+        }
+        self.v = state.v;
+        self.index = state.index;
+        self.pc = state.pc;
+        self.stack.clear();
+        for address in state.stack.iter() {
+            let _ = self.stack.push(*address);
+        }
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        for (pressed, held) in self.key_pressed.iter_mut().zip(state.keypad.iter()) {
+            *pressed = i32::from(*held);
+        }
+        self.vblank = state.vblank;
+        if let Some(rng) = state.rng {
+            self.rng.set_state(rng);
+        }
+        self.redraw_gfx = true;
+        Ok(())
+    }
+
+    /// Draws the processor's graphics memory to `display` if it changed since
+    /// the last call
+    pub fn update_graphics<D: Display>(&mut self, display: &mut D) {
+        if self.redraw_gfx {
+            display.draw(&self.graphics);
+            self.redraw_gfx = false;
         }
     }
 
     // Private functions
-    fn play_sound(&mut self) {
-        println!("BEEP!");
+    /// Space between the load address and the end of memory
+    fn max_rom_size(&self) -> usize {
+        self.memory.len().saturating_sub(self.platform.load_address as usize)
     }
 
-    #[cfg(not(tarpaulin_include))]
-    fn rand() -> u8 {
-        thread_rng().gen::<u8>()
+    fn get_next_opcode(&mut self) -> Result<u16, Chip8Error> {
+        let command = self.opcode_at(self.pc)?;
+        self.pc = self.pc.wrapping_add(2);
+        Ok(command)
     }
 
-    fn get_next_opcode(&mut self) -> (u8,u8,u8,u8) {
-        let pc = self.pc as usize;
-        let command: u16 = (u16::from(self.memory[pc]) << 8) 
-                          | u16::from(self.memory[pc+1]);
-        self.pc += 2;
-        (
-            (command >> 12) as u8 & 0xF,
-            (command >>  8) as u8 & 0xF,
-            (command >>  4) as u8 & 0xF,
-            command         as u8 & 0xF)
+    /// Reads the big-endian opcode stored at `address`
+    fn opcode_at(&self, address: u16) -> Result<u16, Chip8Error> {
+        let location = address as usize;
+        let fault = |location| Chip8Error::MemoryOutOfBounds { location, address };
+        let hi = self.memory.read(location).ok_or_else(|| fault(location))?;
+        let lo = self.memory.read(location + 1).ok_or_else(|| fault(location + 1))?;
+        Ok(u16::from(hi) << 8 | u16::from(lo))
     }
 
-    fn clear_screen(&mut self) {
-        println!("Clearing screen...");
-        self.graphics = [[0;64];32];
-        self.redraw_gfx = true;
+    /// Address of the instruction currently being executed
+    fn current_address(&self) -> u16 {
+        self.pc.wrapping_sub(2)
     }
 
-    fn execute_next_op(&mut self) {
-        self.current_op = self.get_next_opcode();
-        match self.current_op.0 {
-            0x0 => match self.current_op.1 {
-                0x0 => match (self.current_op.2 as u16) << 4 | self.current_op.3 as u16 {
-                    0xE0 => self.clear_screen(), // 0x00E0: clear screen
-                    0xEE => self.op_00ee(),  // 0x00EE: return from subroutine
-                    _ => self.unimplemented(),
-                }
-                _ => self.unimplemented(), // We don't support 0x0NNN instructions 
-            }
-            0x1 => self.op_1nnn(), // 1NNN: Jump to NNN
-            0x2 => self.op_2nnn(), // 2NNN: call subroutine at NNN
-            0x3 => self.op_3xnn(), // 3XNN: skip next instr if V[X] == NN
-            0x4 => self.op_4xnn(), // 4XNN: skip next instr if V[X] != NN
-            0x5 => self.op_5xy0(), // 5XY0: skip next instr if V[X] == V[Y]
-            0x6 => self.op_6xnn(), // 6XNN: set V[X] == NN
-            0x7 => self.op_7xnn(), // 7XNN: set V[X] += NN (carry flag not changed)
-            0x8 => match self.current_op.3 {
-                0x0 => self.op_8xy0(), // 8XY0: V[X] = V[Y]
-                0x1 => self.op_8xy1(), // 8XY1: V[X] = V[X] OR V[Y]
-                0x2 => self.op_8xy2(), // 8XY2: V[X] = V[X] AND V[Y]
-                0x3 => self.op_8xy3(), // 8XY3: V[X] = V[X] XOR V[Y]
-                0x4 => self.op_8xy4(), // 8XY4: V[X] = V[X] + V[Y] (carry flag set to 1 if carry, 0 if not)
-                0x5 => self.op_8xy5(), // 8XY5: V[X] = V[X] - V[Y] (carry flag set to 0 if borrow, 1 if not)
-                0x6 => self.op_8xy6(), // 8XY6: V[F] gets least sig bit of V[X], then VX >>= 1 (right shift)
-                0x7 => self.op_8xy7(), // 8XY7: V[X] = V[Y] - V[X] (carry flag set to 0 if borrow, 1 if not)
-                0xE => self.op_8xye(), // 8XYE: V[F] gets most sig bit of V[X], V[X] <<= 1 (left shift)
-                _ => self.unimplemented(),
-            }
-            0x9 => self.op_9xy0(), // 9XY0: Skip next instr if V[X] != V[Y]
-            0xA => self.op_annn(), // ANNN: Set index to NNN
-            0xB => self.op_bnnn(), // BNNN: PC = V[0] + NNN
-            0xC => self.op_cxnn(), // CXNN: V[X] = rand() AND NN 
-            0xD => self.op_dxyn(), // DXYN: Draw sprite at (V[X],V[Y]), 8px wide x N high
-                                   //       V[F] set to 1 if any screen pixels flipped from set to
-                                   //       unset, 0 if not
-            0xE => match self.current_op.2 {
-                0x9 => self.op_ex9e(), // EX9E: skip next instr if key V[X] is pressed
-                0xA => self.op_exa1(), // EXA1: skip next instr if key V[X] is NOT pressed
-                _ => self.unimplemented(),
-            }
-            0xF => match u16::from(self.current_op.2) << 4 | u16::from(self.current_op.3) {
-                0x07 => self.op_fx07(), // FX07: V[X] = value of delay timer
-                0x0A => self.op_fx0a(), // FX0A: V[X] = key press (blocking wait for key press)
-                0x15 => self.op_fx15(), // FX15: Set delay timer to V[X]
-                0x18 => self.op_fx18(), // FX18: Set sound timer to V[X]
-                0x1e => self.op_fx1e(), // FX1E: Index += V[X]. VF set to 1 if I + V[X} > 0xFFF, 0 if not
-                0x29 => self.op_fx29(), // FX29: Index set to location of hex sprite corresponding to val of V[X]
-                0x33 => self.op_fx33(), // FX33: Store binary-coded decimal representation of V[X] into:
-                                        //   Index[0]: hundreds digit
-                                        //   Index[1]: tens digit
-                                        //   Index[2]: ones digit
-                0x55 => self.op_fx55(), // FX55: store V[0] thru V[X] inclusive in memory starting at Index
-                0x65 => self.op_fx65(), // FX65: load V[0] through V[X] inclusive from memory starting at Index
-                _ => self.unimplemented(),
-            }
-            _ => self.unimplemented(),
+    fn read_memory(&self, location: usize) -> Result<u8, Chip8Error> {
+        match self.memory.read(location) {
+            Some(byte) => Ok(byte),
+            None => Err(Chip8Error::MemoryOutOfBounds { location, address: self.current_address() }),
         }
     }
 
-    fn unimplemented(&self) {
-        let current_op: u16 = (self.current_op.0 as u16) << 12 |
-                              (self.current_op.1 as u16) <<  8 |
-                              (self.current_op.2 as u16) <<  4 |
-                              self.current_op.3 as u16;
-        panic!("Unimplemented Opcode: {:#06x}", current_op);
+    fn write_memory(&mut self, location: usize, byte: u8) -> Result<(), Chip8Error> {
+        if self.memory.write(location, byte) {
+            Ok(())
+        } else {
+            Err(Chip8Error::MemoryOutOfBounds { location, address: self.current_address() })
+        }
     }
 
-    fn construct_address_from_op(&self) -> u16 {
-        u16::from(self.current_op.1) << 8 |
-        u16::from(self.current_op.2) << 4 |
-        u16::from(self.current_op.3)
+    fn clear_screen(&mut self) {
+        self.graphics = [[0;64];32];
+        self.redraw_gfx = true;
+    }
+
+    fn execute_next_op(&mut self) -> Result<(), Chip8Error> {
+        let opcode = self.get_next_opcode()?;
+        let instruction = match decode(opcode) {
+            Ok(instruction) => instruction,
+            Err(err) => return Err(Chip8Error::UnknownOpcode {
+                opcode: err.opcode,
+                address: self.current_address(),
+            }),
+        };
+        self.execute(&instruction)
     }
 
-    fn op_00ee(&mut self) { // return from subroutine
+    fn op_00ee(&mut self) -> Result<(), Chip8Error> { // return from subroutine
         match self.stack.pop() {
-            Some(return_val) => self.pc = return_val,
-            None => println!("Error executing 0x00ee at {:#06x}: nothing on call stack!", self.pc),
+            Ok(return_val) => self.pc = return_val,
+            Err(_) => return Err(Chip8Error::StackUnderflow { address: self.current_address() }),
         };
+        Ok(())
     }
 
-    fn op_1nnn(&mut self) {
-        let addr = self.construct_address_from_op();
-        self.pc = addr;
+    fn op_1nnn(&mut self, nnn: u16) {
+        self.pc = nnn;
     }
 
-    fn op_2nnn(&mut self) {
-        let addr = self.construct_address_from_op();
-        self.stack.push(self.pc);
-        self.pc = addr;
+    fn op_2nnn(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        if let Err(StackError::Overflow) = self.stack.push(self.pc) {
+            return Err(Chip8Error::StackOverflow { address: self.current_address(), depth: self.stack.depth() });
+        }
+        self.pc = nnn;
+        Ok(())
     }
 
-    fn op_3xnn(&mut self) {
-        let x = self.current_op.1 as usize;
-        let nn = self.current_op.2 << 4 | self.current_op.3;
+    fn op_3xnn(&mut self, x: usize, nn: u8) {
         if self.v[x] == nn {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(2);
         }
     }
 
-    fn op_4xnn(&mut self) {
-        let x = self.current_op.1 as usize;
-        let nn = self.current_op.2 << 4 | self.current_op.3;
+    fn op_4xnn(&mut self, x: usize, nn: u8) {
         if self.v[x] != nn {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(2);
         }
     }
 
-    fn op_5xy0(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
+    fn op_5xy0(&mut self, x: usize, y: usize) {
         if self.v[x] == self.v[y] {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(2);
         }
     }
 
-    fn op_6xnn(&mut self) {
-        let x = self.current_op.1 as usize;
-        let nn = self.current_op.2 << 4 | self.current_op.3;
-
+    fn op_6xnn(&mut self, x: usize, nn: u8) {
         self.v[x] = nn;
     }
 
-    fn op_7xnn(&mut self) {
-        let x = self.current_op.1 as usize;
-        let nn = self.current_op.2 << 4 | self.current_op.3;
-        
+    fn op_7xnn(&mut self, x: usize, nn: u8) {
         let result: u16 = self.v[x] as u16 + nn as u16;
         self.v[x] = result as u8;
     }
 
-    fn op_8xy0(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_8xy0(&mut self, x: usize, y: usize) {
         self.v[x] = self.v[y];
     }
 
-    fn op_8xy1(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_8xy1(&mut self, x: usize, y: usize) {
         self.v[x] |= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
 
-    fn op_8xy2(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-        
+    fn op_8xy2(&mut self, x: usize, y: usize) {
         self.v[x] &= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
 
-    fn op_8xy3(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_8xy3(&mut self, x: usize, y: usize) {
         self.v[x] ^= self.v[y];
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
     }
 
 
-    fn op_8xy4(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_8xy4(&mut self, x: usize, y: usize) {
         let result: u16 = self.v[x] as u16 + self.v[y] as u16;
         if result > 0xFF {
             self.v[0xF] = 1;
@@ -392,10 +551,7 @@ impl Cpu {
         self.v[x] = result as u8;
     }
 
-    fn op_8xy5(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_8xy5(&mut self, x: usize, y: usize) {
         if self.v[x] < self.v[y] {
             self.v[0xF] = 0;
         } else {
@@ -405,16 +561,16 @@ impl Cpu {
         self.v[x] = self.v[x].wrapping_sub(self.v[y]);
     }
 
-    fn op_8xy6(&mut self) {
-        let x = self.current_op.1 as usize;
-        self.v[0xF] = self.v[x] & 0x01;
+    fn op_8xy6(&mut self, x: usize, y: usize) {
+        if !self.quirks.shift {
+            self.v[x] = self.v[y];
+        }
+        let flag = self.v[x] & 0x01;
         self.v[x] >>= 1;
+        self.v[0xF] = flag;
     }
 
-    fn op_8xy7(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_8xy7(&mut self, x: usize, y: usize) {
         if self.v[x] <= self.v[y] {
             self.v[0xF] = 1;
         }
@@ -425,52 +581,54 @@ impl Cpu {
         self.v[x] = self.v[y].wrapping_sub(self.v[x]);
     }
 
-    fn op_8xye(&mut self) {
-        let x = self.current_op.1 as usize;
-        self.v[0xF] = self.v[x] >> 7;
+    fn op_8xye(&mut self, x: usize, y: usize) {
+        if !self.quirks.shift {
+            self.v[x] = self.v[y];
+        }
+        let flag = self.v[x] >> 7;
         self.v[x] <<= 1;
+        self.v[0xF] = flag;
     }
 
-    fn op_9xy0(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-
+    fn op_9xy0(&mut self, x: usize, y: usize) {
         if self.v[x] != self.v[y] {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(2);
         }
     }
 
-    fn op_annn(&mut self) {
-        let nnn = self.construct_address_from_op();
-
+    fn op_annn(&mut self, nnn: u16) {
         self.index = nnn;
     }
 
-    fn op_bnnn(&mut self) {
-        let nnn = self.construct_address_from_op();
-        self.pc = nnn + self.v[0] as u16;
+    fn op_bnnn(&mut self, x: usize, nnn: u16) {
+        // With the quirk the high nibble of NNN also selects the register
+        let reg = if self.quirks.jump_with_vx { x } else { 0 };
+        self.pc = nnn + self.v[reg] as u16;
     }
 
-    fn op_cxnn(&mut self) {
-        let x = self.current_op.1 as usize;
-        let nn = self.current_op.2 << 4 | self.current_op.3;
-
-        self.v[x] = Cpu::rand() & nn;
+    fn op_cxnn(&mut self, x: usize, nn: u8) {
+        self.v[x] = self.rng.next_byte() & nn;
     }
 
-    fn op_dxyn(&mut self) {
-        let x = self.current_op.1 as usize;
-        let y = self.current_op.2 as usize;
-        let n = self.current_op.3 as usize;
+    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> Result<(), Chip8Error> {
         let i = self.index as usize;
-        let x_coord = self.v[x] as usize;
-        let y_coord = self.v[y] as usize;
+        let x_coord = self.v[x] as usize % Cpu::GFX_WIDTH;
+        let y_coord = self.v[y] as usize % Cpu::GFX_HEIGHT;
 
         // Draw a sprite starting at (V[X], V[Y]) from memory[I] that is 8px
         // wide by N pixels tall. XOR each bit of graphics memory, if any are flipped
         // from set to unset, V[F] = 1.  otherwise it should be 0
-        // Also needs to wrap around if the sprite would go out of bounds
-        
+        // Parts of the sprite that go out of bounds either wrap around or are
+        // clipped, depending on the quirks
+
+        if self.quirks.display_wait {
+            if !self.vblank {
+                self.pc = self.pc.wrapping_sub(2); // try again once the next frame starts
+                return Ok(());
+            }
+            self.vblank = false;
+        }
+
         self.v[0xF] = 0;
 
         // line loop
@@ -478,42 +636,43 @@ impl Cpu {
             // byte loop
             for bit in 0..8 {
                 // determine if this bit is set by ANDing with a mask bit
-                let x = (x_coord + bit) % Cpu::GFX_WIDTH;
-                let y = (y_coord + line) % Cpu::GFX_HEIGHT;
-                let set = (self.memory[i + line] >> (7 - bit)) & 0x1;
+                let (x, y) = (x_coord + bit, y_coord + line);
+                if self.quirks.clip_sprites && (x >= Cpu::GFX_WIDTH || y >= Cpu::GFX_HEIGHT) {
+                    continue;
+                }
+                let x = x % Cpu::GFX_WIDTH;
+                let y = y % Cpu::GFX_HEIGHT;
+                let set = (self.read_memory(i + line)? >> (7 - bit)) & 0x1;
                 self.v[0xF] |= set & self.graphics[y][x];
                 self.graphics[y][x] ^= set;
             }
         }
         self.redraw_gfx = true;
+        Ok(())
     }
 
-    fn op_ex9e(&mut self) {
-        let x = self.current_op.1 as usize;
-        let key = self.v[x] as usize;
+    fn op_ex9e(&mut self, x: usize) {
+        // Only the low nibble selects a key, as on the COSMAC VIP
+        let key = (self.v[x] & 0xF) as usize;
 
         if self.key_pressed[key] == 1 {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(2);
         }
     }
 
-    fn op_exa1(&mut self) {
-        let x = self.current_op.1 as usize;
-        let key = self.v[x] as usize;
+    fn op_exa1(&mut self, x: usize) {
+        let key = (self.v[x] & 0xF) as usize;
 
         if self.key_pressed[key] != 1 {
-            self.pc += 2;
+            self.pc = self.pc.wrapping_add(2);
         }
     }
 
-    fn op_fx07(&mut self) {
-        let x = self.current_op.1 as usize;
-
+    fn op_fx07(&mut self, x: usize) {
         self.v[x] = self.delay_timer;
     }
 
-    fn op_fx0a(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx0a(&mut self, x: usize) {
         let mut pressed = false;
 
         for key in 0..15 {
@@ -524,23 +683,20 @@ impl Cpu {
             }
         }
         if ! pressed {
-            self.pc -= 2;
+            self.pc = self.pc.wrapping_sub(2);
         }
     }
 
-    fn op_fx15(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx15(&mut self, x: usize) {
         self.delay_timer = self.v[x];
     }
 
-    fn op_fx18(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx18(&mut self, x: usize) {
         self.sound_timer = self.v[x];
     }
 
-    fn op_fx1e(&mut self) {
-        let x = self.current_op.1 as usize;
-        self.index += self.v[x] as u16;
+    fn op_fx1e(&mut self, x: usize) {
+        self.index = self.index.wrapping_add(self.v[x] as u16);
         
         if self.index > 0xFFF {
             self.v[0xF] = 1;
@@ -550,34 +706,42 @@ impl Cpu {
         }    
     }
 
-    fn op_fx29(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx29(&mut self, x: usize) {
         self.index = self.v[x] as u16 * 5;
     }
 
-    fn op_fx33(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx33(&mut self, x: usize) -> Result<(), Chip8Error> {
         let i = self.index as usize;
-        self.memory[i] = self.v[x] / 100;
-        self.memory[i+1] = (self.v[x] / 10) % 10;
-        self.memory[i+2] = self.v[x] % 10;
+        self.write_memory(i, self.v[x] / 100)?;
+        self.write_memory(i+1, (self.v[x] / 10) % 10)?;
+        self.write_memory(i+2, self.v[x] % 10)
     }
 
-    fn op_fx55(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx55(&mut self, x: usize) -> Result<(), Chip8Error> {
         let i = self.index as usize;
 
         for reg in 0..=x {
-            self.memory[i + reg] = self.v[reg];
+            self.write_memory(i + reg, self.v[reg])?;
         }
+        self.increment_index_after_load_store(x);
+        Ok(())
     }
 
-    fn op_fx65(&mut self) {
-        let x = self.current_op.1 as usize;
+    fn op_fx65(&mut self, x: usize) -> Result<(), Chip8Error> {
         let i = self.index as usize;
 
         for reg in 0..=x {
-            self.v[reg] = self.memory[i + reg];
+            self.v[reg] = self.read_memory(i + reg)?;
+        }
+        self.increment_index_after_load_store(x);
+        Ok(())
+    }
+
+    fn increment_index_after_load_store(&mut self, x: usize) {
+        match self.quirks.load_store {
+            IndexIncrement::None => {},
+            IndexIncrement::X => self.index = self.index.wrapping_add(x as u16),
+            IndexIncrement::XPlusOne => self.index = self.index.wrapping_add(x as u16 + 1),
         }
     }
 
@@ -587,6 +751,7 @@ impl Cpu {
 #[cfg(test)]
 mod cpu_tests {
     use super::*;
+    use crate::memory::AddressPolicy;
 
     #[test]
     fn test_new_cpu() {
@@ -598,7 +763,6 @@ mod cpu_tests {
         assert!(cpu.stack.is_empty());
         assert_eq!(0, cpu.sound_timer);
         assert_eq!(0, cpu.delay_timer);
-        assert_eq!((0,0,0,0), cpu.current_op);
         assert_eq!([0;16], cpu.key_pressed);
     }
 
@@ -613,7 +777,6 @@ mod cpu_tests {
         assert!( cpu.stack.is_empty());
         assert_eq!(0, cpu.sound_timer);
         assert_eq!(0, cpu.delay_timer);
-        assert_eq!((0,0,0,0), cpu.current_op);
         assert_eq!([0;16], cpu.key_pressed);
     }
     
@@ -627,6 +790,36 @@ mod cpu_tests {
         assert_eq!(3, cpu.sound_timer);
     }
 
+    #[test]
+    fn test_sound_active() {
+        let mut cpu = Cpu::new();
+        assert!(!cpu.sound_active());
+        cpu.sound_timer = 2;
+        assert!(cpu.sound_active());
+        cpu.decrement_timers();
+        assert!(cpu.sound_active());
+        cpu.decrement_timers();
+        assert!(!cpu.sound_active());
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut cpu = Cpu::new();
+        for addr in (0x200..0x220).step_by(2) {
+            cpu.memory[addr] = 0x70; // 7001: V[0] += 1
+            cpu.memory[addr + 1] = 0x01;
+        }
+        cpu.delay_timer = 5;
+        cpu.set_instructions_per_frame(4);
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.v[0], 4);
+        assert_eq!(cpu.pc, 0x208);
+        assert_eq!(cpu.delay_timer, 4);
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.v[0], 8);
+        assert_eq!(cpu.delay_timer, 3);
+    }
+
     #[test]
     fn test_load_rom() {
         let base_path = env!("CARGO_MANIFEST_DIR");
@@ -639,25 +832,96 @@ mod cpu_tests {
     }
 
     #[test]
-    #[should_panic]
+    fn test_load_rom_bytes() {
+        let mut cpu = Cpu::new();
+        cpu.v[3] = 7;
+        assert!(cpu.load_rom_bytes(&[0x12, 0x34, 0x56]).is_ok());
+        assert_eq!(&cpu.memory[0x200..0x204], &[0x12, 0x34, 0x56, 0x00]);
+        assert_eq!(cpu.v[3], 0);
+        let rom = vec![0xAA; 0x1000 - 0x200];
+        assert!(cpu.load_rom_bytes(&rom).is_ok());
+        let rom = vec![0xAA; 0x1000 - 0x200 + 1];
+        assert!(matches!(cpu.load_rom_bytes(&rom),
+                         Err(Chip8Error::RomTooLarge { size: 0xe01, max: 0xe00 })));
+    }
+
+    #[test]
+    fn test_load_rom_reader_short_reads() {
+        // Hands out one byte per read call
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                match (self.0.split_first(), buf.first_mut()) {
+                    (Some((byte, rest)), Some(slot)) => {
+                        *slot = *byte;
+                        self.0 = rest;
+                        Ok(1)
+                    },
+                    _ => Ok(0),
+                }
+            }
+        }
+        let mut cpu = Cpu::new();
+        assert!(cpu.load_rom_reader(Trickle(&[0x60, 0x01, 0x61, 0x02])).is_ok());
+        assert_eq!(&cpu.memory[0x200..0x204], &[0x60, 0x01, 0x61, 0x02]);
+    }
+
+    #[test]
+    fn test_load_rom_eti_660() {
+        let mut cpu = Cpu::with_platform(Platform::ETI_660, Quirks::default()).unwrap();
+        assert_eq!(cpu.pc, 0x600);
+        assert!(cpu.load_rom_bytes(&[0x12, 0x34]).is_ok());
+        assert_eq!(&cpu.memory[0x600..0x602], &[0x12, 0x34]);
+        assert_eq!(cpu.memory[0x200], 0);
+        assert_eq!(cpu.pc, 0x600);
+        let rom = vec![0; 0x1000 - 0x600 + 1];
+        assert!(matches!(cpu.load_rom_bytes(&rom), Err(Chip8Error::RomTooLarge { max: 0xa00, .. })));
+    }
+
+    #[test]
+    fn test_load_address_outside_memory() {
+        let platform = Platform { memory_size: 0x400, ..Platform::ETI_660 };
+        assert!(matches!(Cpu::with_platform(platform, Quirks::default()), Err(Chip8Error::InvalidPlatform(_))));
+        let platform = Platform { memory_size: 0x601, ..Platform::ETI_660 };
+        let mut cpu = Cpu::with_platform(platform, Quirks::default()).unwrap();
+        assert!(cpu.load_rom_bytes(&[]).is_ok());
+        assert!(cpu.load_rom_bytes(&[0x12]).is_ok());
+    }
+
+    #[test]
+    fn test_load_rom_reader_stops_early() {
+        // An endless ROM is rejected once it has grown past the maximum
+        let mut cpu = Cpu::new();
+        let result = cpu.load_rom_reader(std::io::repeat(0xAA));
+        assert!(matches!(result, Err(Chip8Error::RomTooLarge { size: 0xe01, max: 0xe00 })));
+        assert_eq!(cpu.memory[0x200], 0);
+    }
+
+    #[test]
     fn test_load_rom_bigfile() {
         let base_path = env!("CARGO_MANIFEST_DIR");
         let fpath = format!("{}/{}", base_path, "big_file.ch8");
         let mut cpu = Cpu::new();
-        assert!(cpu.load_rom(&fpath.to_string()).is_err()); //this line panics
+        assert!(matches!(cpu.load_rom(&fpath.to_string()),
+                         Err(Chip8Error::RomTooLarge { .. })));
     }
 
     #[test]
     fn test_button_presses() {
         let mut cpu = Cpu::new();
-        let button1: usize = 2;
-        let button2: usize = 1;
+        let button1 = Key::Num2;
+        let button2 = Key::Num1;
         cpu.press_button(button1);
         cpu.press_button(button2);
-        assert_eq!(cpu.key_pressed[button1], 1);
+        assert_eq!(cpu.key_pressed[2], 1);
+        assert!(cpu.is_pressed(button1));
         cpu.release_button(button1);
-        assert_eq!(cpu.key_pressed[button1], 0);
-        assert_eq!(cpu.key_pressed[button2], 1);
+        assert_eq!(cpu.key_pressed[2], 0);
+        assert!(!cpu.is_pressed(button1));
+        assert_eq!(cpu.key_pressed[1], 1);
+        let keypad = cpu.keypad();
+        assert!(keypad[1] && !keypad[2]);
+        assert_eq!(keypad.iter().filter(|held| **held).count(), 1);
     }
 
     #[test]
@@ -666,9 +930,21 @@ mod cpu_tests {
         cpu.reset();
         cpu.memory[0x200] = 0x12;
         cpu.memory[0x201] = 0x4e;
-        let next_op = cpu.get_next_opcode();
+        let next_op = cpu.get_next_opcode().unwrap();
         assert_eq!(cpu.pc, 0x202);
-        assert_eq!(next_op, (0x1,0x2,0x4,0xe) );
+        assert_eq!(next_op, 0x124e);
+    }
+
+    #[test]
+    fn test_peek_and_execute() {
+        let mut cpu = Cpu::new();
+        cpu.memory[0x200] = 0x6A;
+        cpu.memory[0x201] = 0x42;
+        let instruction = cpu.peek_instruction().unwrap();
+        assert_eq!(instruction, Instruction::LoadImm { x: 0xA, nn: 0x42 });
+        assert_eq!(cpu.pc, 0x200); // peeking doesn't advance
+        cpu.execute(&instruction).unwrap();
+        assert_eq!(cpu.v[0xA], 0x42);
     }
 
     #[test]
@@ -682,86 +958,96 @@ mod cpu_tests {
         assert_eq!(cpu.graphics, [[0;64];32]);
     }
 
+    #[test]
+    fn test_update_graphics() {
+        struct CountingDisplay { frames: usize, last: Framebuffer }
+        impl Display for CountingDisplay {
+            fn draw(&mut self, graphics: &Framebuffer) {
+                self.frames += 1;
+                self.last = *graphics;
+            }
+        }
+
+        let mut display = CountingDisplay { frames: 0, last: [[0;64];32] };
+        let mut cpu = Cpu::new();
+        cpu.update_graphics(&mut display);
+        assert_eq!(display.frames, 0); // nothing changed, nothing drawn
+
+        cpu.graphics[3][7] = 1;
+        cpu.redraw_gfx = true;
+        assert!(cpu.needs_redraw());
+        cpu.update_graphics(&mut display);
+        assert_eq!(display.frames, 1);
+        assert_eq!(display.last[3][7], 1);
+        assert!(!cpu.needs_redraw());
+        cpu.update_graphics(&mut display);
+        assert_eq!(display.frames, 1);
+    }
+
     #[test]
     fn test_op_00ee() {
         let mut cpu = Cpu::new();
-        cpu.stack.push(0x206);
-        cpu.op_00ee();
+        cpu.stack.push(0x206).unwrap();
+        assert!(cpu.op_00ee().is_ok());
         assert!(cpu.stack.is_empty());
         assert_eq!(cpu.pc, 0x206);
-        cpu.op_00ee(); //should emit some text but still work, pc should be same
+        // empty stack should report an underflow, pc should be same
+        assert!(matches!(cpu.op_00ee(), Err(Chip8Error::StackUnderflow { address: 0x204 })));
         assert_eq!(cpu.pc, 0x206);
     }
 
     #[test]
     fn test_op_1nnn() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0x1,0xA,0xB,0xC);
-        cpu.op_1nnn();
+        cpu.op_1nnn(0xABC);
         assert_eq!(cpu.pc, 0x0ABC);
     }
     
-    #[test]
-    fn test_construct_address_from_op() {
-        let mut cpu = Cpu::new();
-        cpu.current_op = (0x0, 0x1, 0x2, 0x3);
-        let addr = cpu.construct_address_from_op();
-        assert_eq!(addr, 0x0123);
-    }
-    
     #[test]
     fn test_op_2nnn_3xnn_4xnn_5xy0() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0x2,0x2,0x2,0x2);
-        cpu.op_2nnn();
+        cpu.op_2nnn(0x222).unwrap();
         assert_eq!(cpu.stack[0], 0x200);
         assert_eq!(cpu.pc, 0x222);
         assert!( cpu.stack.len() == 1 );
 
         cpu.reset();
-        let op1 = (0x3,0x1,0x2,0x2);
-        let op2 = (0x3,0x1,0x4,0x4);
 
         cpu.v[0x1] = 0x44;
         cpu.v[0xE] = 0x43;
-        cpu.current_op = op1;
-        cpu.op_3xnn(); // since v[1] == 0x44, shouldn't skip.
+        cpu.op_3xnn(0x1, 0x22); // since v[1] == 0x44, shouldn't skip.
         assert_eq!(cpu.pc, 0x200);
-        cpu.op_4xnn(); // since v[1] != 0x22, should skip.
+        cpu.op_4xnn(0x1, 0x22); // since v[1] != 0x22, should skip.
         assert_eq!(cpu.pc, 0x202);
 
-        cpu.current_op = op2;
-        cpu.op_3xnn(); // v1 == NN, skip
+        cpu.op_3xnn(0x1, 0x44); // v1 == NN, skip
         assert_eq!(cpu.pc, 0x204);
-        cpu.op_4xnn(); // v1 == NN, don't skip
+        cpu.op_4xnn(0x1, 0x44); // v1 == NN, don't skip
         assert_eq!(cpu.pc, 0x204);
 
-        cpu.current_op = (0x5,0x1,0xE,0x0);
-        cpu.op_5xy0();
+        cpu.op_5xy0(0x1, 0xE);
         assert_eq!(cpu.pc, 0x204);
         cpu.v[0xE] = 0x44;
-        cpu.op_5xy0();
+        cpu.op_5xy0(0x1, 0xE);
         assert_eq!(cpu.pc, 0x206);
     }
 
     #[test]
     fn test_op_6xnn() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0x6, 0xD, 0xA, 0xD);
-        cpu.op_6xnn();
+        cpu.op_6xnn(0xD, 0xAD);
         assert_eq!(cpu.v[0xD], 0xAD);
     }
 
     #[test]
     fn test_op_7xnn() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0x7,0x2,0x0,0x3);
         cpu.v[2] = 3;
         let vf_state = cpu.v[0xF];
-        cpu.op_7xnn();
+        cpu.op_7xnn(0x2, 0x03);
         assert_eq!(cpu.v[2],6);
         cpu.v[2] = 253;
-        cpu.op_7xnn();
+        cpu.op_7xnn(0x2, 0x03);
         assert_eq!(cpu.v[2],0); // we should have overflowed here
         assert_eq!(cpu.v[0xF],vf_state); //...but carry shouldn't be affected
     }
@@ -769,53 +1055,48 @@ mod cpu_tests {
     #[test]
     fn test_op_8xy0() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,0,1,0);
         cpu.v[0] = 40;
         cpu.v[1] = 0;
-        cpu.op_8xy0();
+        cpu.op_8xy0(0x0, 0x1);
         assert_eq!(cpu.v[0], cpu.v[1]);
     }
 
     #[test]
     fn test_op_8xy1() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,0,1,1);
         cpu.v[0] = 0x25;
         cpu.v[1] = 0x30;
-        cpu.op_8xy1();
+        cpu.op_8xy1(0x0, 0x1);
         assert_eq!(cpu.v[0], 0x35);
     }
 
     #[test]
     fn test_op_8xy2() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,0,1,2);
         cpu.v[0] = 0x25;
         cpu.v[1] = 0x30;
-        cpu.op_8xy2();
+        cpu.op_8xy2(0x0, 0x1);
         assert_eq!(cpu.v[0], 0x20);
     }
 
     #[test]
     fn test_op_8xy3() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,0,1,3);
         cpu.v[0] = 0x25;
         cpu.v[1] = 0x30;
-        cpu.op_8xy3();
+        cpu.op_8xy3(0x0, 0x1);
         assert_eq!(cpu.v[0], 0x15);
     }
 
     #[test]
     fn test_op_8xy4() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,0,1,4);
         cpu.v[0] = 250;
         cpu.v[1] = 3;
-        cpu.op_8xy4();
+        cpu.op_8xy4(0x0, 0x1);
         assert_eq!(cpu.v[0], 253);
         assert_eq!(cpu.v[0xF], 0);
-        cpu.op_8xy4();
+        cpu.op_8xy4(0x0, 0x1);
         assert_eq!(cpu.v[0], 0); //should have carried here
         assert_eq!(cpu.v[0xF], 1); //...and set the carry flag
     }
@@ -823,13 +1104,12 @@ mod cpu_tests {
     #[test]
     fn test_op_8xy5() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,0,1,5);
         cpu.v[0] = 3;
         cpu.v[1] = 3;
-        cpu.op_8xy5();
+        cpu.op_8xy5(0x0, 0x1);
         assert_eq!(cpu.v[0], 0);
         assert_eq!(cpu.v[0xF], 1); //no borrow here
-        cpu.op_8xy5();
+        cpu.op_8xy5(0x0, 0x1);
         assert_eq!(cpu.v[0], 253);
         assert_eq!(cpu.v[0xF], 0); //we borrowed, carry flag should not be set
     }
@@ -837,34 +1117,63 @@ mod cpu_tests {
     #[test]
     fn test_op_8xy6() {
         let mut cpu = Cpu::new();
-        let op1 = (8,0,2,6);
-        let op2 = (8,1,2,6);
         cpu.v[0] = 0x80; // right shifting this will not set V[F]
         cpu.v[1] = 0x01; // right shifting this should set V[F]
-        cpu.current_op = op1;
-        cpu.op_8xy6();
+        cpu.op_8xy6(0x0, 0x2);
         assert_eq!(cpu.v[0], 0x40);
         assert_eq!(cpu.v[0xF], 0);
-        cpu.current_op = op2;
-        cpu.op_8xy6();
+        cpu.op_8xy6(0x1, 0x2);
         assert_eq!(cpu.v[1], 0x0);
         assert_eq!(cpu.v[0xF], 1);
     }
 
+    #[test]
+    fn test_op_8xy6_8xye_shift_quirk() {
+        let mut cpu = Cpu::with_quirks(Quirks::COSMAC_VIP);
+        cpu.v[0] = 0xFF;
+        cpu.v[2] = 0x81;
+        // V0 = V2 >> 1
+        cpu.op_8xy6(0x0, 0x2);
+        assert_eq!(cpu.v[0], 0x40);
+        assert_eq!(cpu.v[0xF], 1);
+        // V0 = V2 << 1
+        cpu.op_8xye(0x0, 0x2);
+        assert_eq!(cpu.v[0], 0x02);
+        assert_eq!(cpu.v[0xF], 1);
+        assert_eq!(cpu.v[2], 0x81);
+    }
+
+    #[test]
+    fn test_op_8xy1_8xy2_8xy3_vf_reset_quirk() {
+        let mut cpu = Cpu::with_quirks(Quirks::COSMAC_VIP);
+        for op in 1..=3 {
+            cpu.v[0xF] = 1;
+            match op {
+                1 => cpu.op_8xy1(0x0, 0x1),
+                2 => cpu.op_8xy2(0x0, 0x1),
+                _ => cpu.op_8xy3(0x0, 0x1),
+            }
+            assert_eq!(cpu.v[0xF], 0);
+        }
+        cpu.set_quirks(Quirks::SUPER_CHIP);
+        cpu.v[0xF] = 1;
+        cpu.op_8xy3(0x0, 0x1);
+        assert_eq!(cpu.v[0xF], 1);
+    }
+
     #[test]
     fn test_op_8xy7() {
         // This test is the same as 8xy5, just subtracting y from x instead
         // As such, i am using the same test as 8xy5 with the registers reversed
         let mut cpu = Cpu::new();
-        cpu.current_op = (8,1,0,7);
         cpu.v[0] = 3;
         cpu.v[1] = 3;
-        cpu.op_8xy7();
+        cpu.op_8xy7(0x1, 0x0);
         assert_eq!(cpu.v[1], 0);
         assert_eq!(cpu.v[0xF], 1); //no borrow here
         cpu.v[0] = 0;
         cpu.v[1] = 3;
-        cpu.op_8xy7();
+        cpu.op_8xy7(0x1, 0x0);
         assert_eq!(cpu.v[1], 253);
         assert_eq!(cpu.v[0xF], 0); //we borrowed, carry flag should not be set
     }
@@ -873,16 +1182,12 @@ mod cpu_tests {
     fn test_op_8xye() {
         // ditto for this test.  Very similar to 8xy6 but with left shifts
         let mut cpu = Cpu::new();
-        let op1 = (8,0,2,0xE);
-        let op2 = (8,1,2,0xE);
         cpu.v[0] = 0x80; // 0x80 << 1 = 0x00, V[F] = 1
         cpu.v[1] = 0x01; // 0x01 << 1 = 0x02, V[F] = 0
-        cpu.current_op = op1;
-        cpu.op_8xye();
+        cpu.op_8xye(0x0, 0x2);
         assert_eq!(cpu.v[0], 0x00);
         assert_eq!(cpu.v[0xF], 1);
-        cpu.current_op = op2;
-        cpu.op_8xye();
+        cpu.op_8xye(0x1, 0x2);
         assert_eq!(cpu.v[1], 0x02);
         assert_eq!(cpu.v[0xF], 0);
     }
@@ -890,45 +1195,71 @@ mod cpu_tests {
     #[test]
     fn test_op_9xy0() {
         let mut cpu = Cpu::new();
-        let op1 = (9,0,0xE,0x0);
-        let op2 = (9,1,0xE,0x0);
         cpu.v[0] = 0x55;
         cpu.v[0xE] = 0x55;
         cpu.pc = 0x400; // changing program counter from default
         
-        cpu.current_op = op1;
-        cpu.op_9xy0();
+        cpu.op_9xy0(0x0, 0xE);
         assert_eq!(cpu.pc, 0x400);
-        cpu.current_op = op2;
         cpu.v[0] = 0x54;
-        cpu.op_9xy0();
+        cpu.op_9xy0(0x1, 0xE);
         assert_eq!(cpu.pc, 0x402);
     }
 
     #[test]
     fn test_op_annn() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xA,4,5,6);
-        cpu.op_annn();
+        cpu.op_annn(0x456);
         assert_eq!(cpu.index, 0x456);
     }
 
     #[test]
     fn test_op_bnnn() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xB,3,0,0);
         cpu.v[0] = 5;
-        cpu.op_bnnn();
+        cpu.op_bnnn(0x3, 0x300);
         assert_eq!(cpu.pc, 0x305);
     }
 
+    #[test]
+    fn test_op_bnnn_jump_with_vx_quirk() {
+        let mut cpu = Cpu::with_quirks(Quirks::SUPER_CHIP);
+        cpu.v[0] = 5;
+        cpu.v[3] = 7;
+        cpu.op_bnnn(0x3, 0x300);
+        assert_eq!(cpu.pc, 0x307);
+    }
+
     #[test]
     fn test_op_cxnn() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xC,1,2,4);
         cpu.v[1] = 0x20;
-        cpu.op_cxnn();
-        // kinda hard to test random results...
+        cpu.op_cxnn(0x1, 0x24);
+        assert_eq!(cpu.v[1] & !0x24, 0); // masked by NN
+
+        // the same seed gives the same sequence
+        let mut other = Cpu::new();
+        cpu.set_seed(42);
+        other.set_seed(42);
+        for _ in 0..16 {
+            cpu.op_cxnn(0x1, 0xFF);
+            other.op_cxnn(0x1, 0xFF);
+            assert_eq!(cpu.v[1], other.v[1]);
+        }
+    }
+
+    #[test]
+    fn test_set_random_source() {
+        #[derive(Debug)]
+        struct Constant(u8);
+        impl RandomSource for Constant {
+            fn next_byte(&mut self) -> u8 { self.0 }
+        }
+
+        let mut cpu = Cpu::new();
+        cpu.set_random_source(Box::new(Constant(0xAB)));
+        cpu.op_cxnn(0x2, 0x0F);
+        assert_eq!(cpu.v[2], 0x0B);
     }
 
     #[test]
@@ -943,8 +1274,7 @@ mod cpu_tests {
         cpu.graphics[1][0] = 1; // will be set
         cpu.graphics[1][1] = 0; // will be unset
          
-        cpu.current_op = (0xD,0,0,2); 
-        cpu.op_dxyn();
+        cpu.op_dxyn(0x0, 0x0, 2).unwrap();
         assert_eq!(cpu.graphics[0][0], 0);
         assert_eq!(cpu.graphics[0][1], 1);
         assert_eq!(cpu.graphics[1][0], 1);
@@ -961,8 +1291,7 @@ mod cpu_tests {
         cpu.graphics[0][62] = 1; //this bit is unset, set v[f] = 1
         cpu.graphics[0][63] = 0;
 
-        cpu.current_op = (0xD,1,0,1);
-        cpu.op_dxyn();
+        cpu.op_dxyn(0x1, 0x0, 1).unwrap();
         assert_eq!(cpu.graphics[0][62], 0);
         assert_eq!(cpu.graphics[0][63], 1);
         assert_eq!(cpu.graphics[0][0], 1);
@@ -984,8 +1313,7 @@ mod cpu_tests {
         cpu.v[1] = 0;
         cpu.v[2] = 30;
 
-        cpu.current_op = (0xD,1,2,3);
-        cpu.op_dxyn();
+        cpu.op_dxyn(0x1, 0x2, 3).unwrap();
         assert_eq!(cpu.graphics[30][0], 1);
         assert_eq!(cpu.graphics[31][0], 1);
         assert_eq!(cpu.graphics[0][0], 1);
@@ -994,39 +1322,88 @@ mod cpu_tests {
 
     
 
+    #[test]
+    fn test_op_dxyn_clipping_quirk() {
+        let mut cpu = Cpu::with_quirks(Quirks::SUPER_CHIP);
+        cpu.index = 0x300;
+        cpu.memory[0x300] = 0xFF;
+        cpu.memory[0x301] = 0xFF;
+        cpu.v[1] = 62;
+        cpu.v[2] = 31;
+        cpu.op_dxyn(0x1, 0x2, 2).unwrap();
+        assert_eq!(cpu.graphics[31][62], 1);
+        assert_eq!(cpu.graphics[31][63], 1);
+        assert_eq!(cpu.graphics[31][0], 0); // clipped, not wrapped
+        assert_eq!(cpu.graphics[0][62], 0);
+
+        // the starting position still wraps
+        cpu.graphics = [[0;64];32];
+        cpu.v[1] = 64 + 4;
+        cpu.v[2] = 32 + 1;
+        cpu.op_dxyn(0x1, 0x2, 2).unwrap();
+        assert_eq!(cpu.graphics[1][4], 1);
+    }
+
+    #[test]
+    fn test_op_dxyn_display_wait_quirk() {
+        let mut cpu = Cpu::with_quirks(Quirks::COSMAC_VIP);
+        cpu.index = 0x300;
+        cpu.memory[0x300] = 0x80;
+        cpu.pc = 0x202;
+        cpu.op_dxyn(0x0, 0x0, 1).unwrap(); // no frame has started yet, wait
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.graphics[0][0], 0);
+        cpu.decrement_timers();
+        cpu.pc = 0x202;
+        cpu.op_dxyn(0x0, 0x0, 1).unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.graphics[0][0], 1);
+        cpu.op_dxyn(0x0, 0x0, 1).unwrap(); // second sprite in the same frame waits
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.graphics[0][0], 1);
+    }
+
     #[test]
     fn test_op_ex9e() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xE,0,0x9,0xE);
         cpu.v[0] = 5;
         cpu.key_pressed[5] = 1;
-        cpu.op_ex9e();
+        cpu.op_ex9e(0x0);
         assert_eq!(cpu.pc, 0x202);
         cpu.key_pressed[5] = 0;
-        cpu.op_ex9e();
+        cpu.op_ex9e(0x0);
         assert_eq!(cpu.pc, 0x202);
     }
 
     #[test]
     fn test_op_exa1() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xE,0,0xA,0x1);
         cpu.v[0] = 5;
         cpu.key_pressed[5] = 1;
-        cpu.op_exa1();
+        cpu.op_exa1(0x0);
         assert_eq!(cpu.pc, 0x200);
         cpu.key_pressed[5] = 0;
-        cpu.op_exa1();
+        cpu.op_exa1(0x0);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_key_skip_masks_register() {
+        let mut cpu = Cpu::new();
+        cpu.v[0] = 0xFF;
+        cpu.key_pressed[0xF] = 1;
+        cpu.op_ex9e(0x0);
+        assert_eq!(cpu.pc, 0x202);
+        cpu.op_exa1(0x0);
         assert_eq!(cpu.pc, 0x202);
     }
 
     #[test]
     fn test_op_fx07() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,0,0,7);
         cpu.v[0] = 0x2;
         cpu.delay_timer = 0xEF;
-        cpu.op_fx07();
+        cpu.op_fx07(0x0);
         assert_eq!(cpu.v[0], 0xEF);
     }
 
@@ -1038,7 +1415,6 @@ mod cpu_tests {
         use std::sync::Mutex;
 
         let cpu = Arc::new(Mutex::new(Cpu::new()));
-        cpu.lock().unwrap().current_op = (0xF,1,0,0xA);
         
         // clone reference to cpu to pass to thread
         let cpu_ref = cpu.clone();
@@ -1050,13 +1426,13 @@ mod cpu_tests {
         });
 
         // button should not be pressed here
-        cpu.lock().unwrap().op_fx0a();
+        cpu.lock().unwrap().op_fx0a(0x1);
         assert_eq!(cpu.lock().unwrap().v[1], 0);
         assert_eq!(cpu.lock().unwrap().pc, 0x1FE);
         thread::sleep(Duration::from_millis(500));
 
         // 500 ms has passed, button should now be pressed
-        cpu.lock().unwrap().op_fx0a();
+        cpu.lock().unwrap().op_fx0a(0x1);
         t.join().unwrap();
         assert_eq!(cpu.lock().unwrap().v[1], 5);
         assert_eq!(cpu.lock().unwrap().pc, 0x1FE);
@@ -1065,55 +1441,62 @@ mod cpu_tests {
     #[test]
     fn test_op_fx15() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,0,1,5);
         cpu.v[0] = 0x1F;
-        cpu.op_fx15();
+        cpu.op_fx15(0x0);
         assert_eq!(cpu.delay_timer, 0x1F);
     }
 
     #[test]
     fn test_op_fx18() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,0xB,1,8);
         cpu.v[0xB] = 0xAC;
-        cpu.op_fx18();
+        cpu.op_fx18(0xB);
         assert_eq!(cpu.sound_timer, 0xAC);
     }
 
     #[test]
     fn test_op_fx1e() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,0,1,0xE);
         cpu.v[0] = 0x04;
         cpu.index = 0xFFD;
-        cpu.op_fx1e();
+        cpu.op_fx1e(0x0);
         assert_eq!(cpu.index, 0x1001);
         assert_eq!(cpu.v[0xF], 1);
         cpu.index = 0xFF2;
-        cpu.op_fx1e();
+        cpu.op_fx1e(0x0);
         assert_eq!(cpu.index, 0xFF6);
         assert_eq!(cpu.v[0xF], 0);
     }
 
+    #[test]
+    fn test_address_arithmetic_wraps() {
+        let mut cpu = Cpu::new();
+        cpu.v[0] = 0x02;
+        cpu.index = 0xFFFF;
+        cpu.op_fx1e(0x0);
+        assert_eq!(cpu.index, 0x0001);
+        cpu.pc = 0xFFFE;
+        cpu.op_3xnn(0x0, 0x02);
+        assert_eq!(cpu.pc, 0x0000);
+    }
+
     #[test]
     fn test_op_fx29() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,0,2,9);
         cpu.v[0] = 1;
-        cpu.op_fx29();
+        cpu.op_fx29(0x0);
         assert_eq!(cpu.index, 0x005);
         cpu.v[0] = 0xF;
-        cpu.op_fx29();
+        cpu.op_fx29(0x0);
         assert_eq!(cpu.index, 0x04B);
     }
 
     #[test]
     fn test_op_fx33() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,0xC,3,3);
         cpu.v[0xC] = 215;
         cpu.index = 0x400;
-        cpu.op_fx33();
+        cpu.op_fx33(0xC).unwrap();
         assert_eq!(cpu.memory[0x400], 2);
         assert_eq!(cpu.memory[0x401], 1);
         assert_eq!(cpu.memory[0x402], 5);
@@ -1122,27 +1505,39 @@ mod cpu_tests {
     #[test]
     fn test_op_fx55() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,2,5,5);
         cpu.v[0] = 1;
         cpu.v[1] = 4;
         cpu.v[2] = 8;
         cpu.index = 0x888;
-        cpu.op_fx55();
+        cpu.op_fx55(0x2).unwrap();
         assert_eq!(cpu.memory[0x888], 1);
         assert_eq!(cpu.memory[0x889], 4);
         assert_eq!(cpu.memory[0x88A], 8);
     }
 
+    #[test]
+    fn test_op_fx55_fx65_load_store_quirk() {
+        let mut cpu = Cpu::with_quirks(Quirks::COSMAC_VIP);
+        cpu.index = 0x800;
+        cpu.op_fx55(0x2).unwrap();
+        assert_eq!(cpu.index, 0x803);
+        cpu.set_quirks(Quirks::CHIP_48);
+        cpu.op_fx65(0x2).unwrap();
+        assert_eq!(cpu.index, 0x805);
+        cpu.set_quirks(Quirks::SUPER_CHIP);
+        cpu.op_fx65(0x2).unwrap();
+        assert_eq!(cpu.index, 0x805);
+    }
+
     #[test]
     fn test_op_fx65() {
         let mut cpu = Cpu::new();
-        cpu.current_op = (0xF,3,6,5);
         cpu.index = 0x800;
         cpu.memory[0x800] = 1;
         cpu.memory[0x801] = 5;
         cpu.memory[0x802] = 9;
         cpu.memory[0x803] = 17;
-        cpu.op_fx65();
+        cpu.op_fx65(0x3).unwrap();
         assert_eq!(cpu.v[0], 1);
         assert_eq!(cpu.v[1], 5);
         assert_eq!(cpu.v[2], 9);
@@ -1150,11 +1545,160 @@ mod cpu_tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_unimplemented() {
         let mut cpu = Cpu::new();
         cpu.memory[0x200] = 0x01; // 0x0NNN Jump to SYS memory not supported
         cpu.memory[0x201] = 0x23;
-        cpu.advance_state();
+        match cpu.advance_state() {
+            Err(Chip8Error::UnknownOpcode { opcode, address }) => {
+                assert_eq!(opcode, 0x0123);
+                assert_eq!(address, 0x200);
+            },
+            other => panic!("expected unknown opcode, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_op_2nnn_stack_overflow() {
+        let mut cpu = Cpu::new();
+        for _ in 0..Stack::DEFAULT_DEPTH {
+            assert!(cpu.op_2nnn(0x200).is_ok());
+        }
+        assert!(matches!(cpu.op_2nnn(0x200), Err(Chip8Error::StackOverflow { depth: 16, .. })));
+        assert_eq!(cpu.stack.len(), Stack::DEFAULT_DEPTH);
+    }
+
+    #[test]
+    fn test_step_through_display_wait() {
+        let mut cpu = Cpu::with_quirks(Quirks::COSMAC_VIP);
+        cpu.load_rom_bytes(&[0xD0, 0x05, 0xD0, 0x05]).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_platform_memory_size() {
+        let platform = Platform { memory_size: 64, ..Platform::default() };
+        assert!(matches!(Cpu::with_platform(platform, Quirks::default()), Err(Chip8Error::InvalidPlatform(_))));
+        let platform = Platform { memory_size: Memory::MAX_SIZE + 1, ..Platform::default() };
+        assert!(Cpu::with_platform(platform, Quirks::default()).is_err());
+        let platform = Platform { memory_size: 0x51, load_address: 0x50, ..Platform::default() };
+        assert!(Cpu::with_platform(platform, Quirks::default()).is_ok());
+    }
+
+    #[test]
+    fn test_platform_stack_depth() {
+        let platform = Platform { stack_depth: 2, ..Platform::default() };
+        let mut cpu = Cpu::with_platform(platform, Quirks::default()).unwrap();
+        assert_eq!(cpu.stack_depth(), 2);
+        cpu.op_2nnn(0x300).unwrap();
+        cpu.op_2nnn(0x400).unwrap();
+        assert!(matches!(cpu.op_2nnn(0x500), Err(Chip8Error::StackOverflow { depth: 2, .. })));
+        cpu.reset();
+        assert!(cpu.stack.is_empty());
+        assert_eq!(cpu.stack_depth(), 2);
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut cpu = Cpu::new();
+        cpu.index = 0xFFE;
+        assert!(matches!(cpu.op_fx55(0x2), Err(Chip8Error::MemoryOutOfBounds { location: 0x1000, .. })));
+        assert_eq!(cpu.memory[0xFFF], cpu.v[1]); // last byte is addressable
+    }
+
+    #[test]
+    fn test_memory_wrapping() {
+        let platform = Platform { address_policy: AddressPolicy::Wrap, ..Platform::default() };
+        let mut cpu = Cpu::with_platform(platform, Quirks::default()).unwrap();
+        cpu.v[2] = 0x42;
+        cpu.index = 0xFFE;
+        cpu.op_fx55(0x2).unwrap();
+        assert_eq!(cpu.memory[0x000], 0x42);
+
+        cpu.index = 0xFFF;
+        cpu.op_fx33(0x2).unwrap(); // 66 -> 0, 6, 6
+        assert_eq!(cpu.memory[0xFFF], 0);
+        assert_eq!(cpu.memory[0x000], 6);
+        assert_eq!(cpu.memory[0x001], 6);
+    }
+
+    #[test]
+    fn test_fetch_at_end_of_memory() {
+        let mut cpu = Cpu::new();
+        cpu.memory[0xFFE] = 0x60; // 6055: V[0] = 0x55
+        cpu.memory[0xFFF] = 0x55;
+        cpu.pc = 0xFFE;
+        cpu.advance_state().unwrap();
+        assert_eq!(cpu.v[0], 0x55);
+        assert!(matches!(cpu.advance_state(),
+                         Err(Chip8Error::MemoryOutOfBounds { location: 0x1000, address: 0x1000 })));
+    }
+
+    #[test]
+    fn test_large_memory() {
+        let platform = Platform { memory_size: Memory::MAX_SIZE, ..Platform::default() };
+        let mut cpu = Cpu::with_platform(platform, Quirks::default()).unwrap();
+        cpu.v[0] = 0x99;
+        cpu.index = 0xFFFF;
+        cpu.op_fx55(0x0).unwrap();
+        assert_eq!(cpu.memory[0xFFFF], 0x99);
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let mut cpu = Cpu::new();
+        cpu.set_seed(7);
+        cpu.load_rom_bytes(&[0x22, 0x06, 0x00, 0xE0, 0x00, 0x00, 0xC0, 0xFF, 0xD0, 0x05]).unwrap();
+        cpu.advance_state().unwrap(); // call 0x206
+        cpu.press_button(Key::A);
+        cpu.delay_timer = 9;
+        let state = cpu.snapshot();
+        assert_eq!(state.stack, vec![0x202]);
+        assert!(state.keypad[0xA]);
+
+        cpu.advance_state().unwrap(); // random
+        cpu.advance_state().unwrap(); // draw
+        let v0 = cpu.v[0];
+        let graphics = cpu.graphics;
+        cpu.reset();
+
+        cpu.restore(&state).unwrap();
+        assert_eq!(cpu.snapshot(), state);
+        assert!(cpu.is_pressed(Key::A));
+        cpu.advance_state().unwrap();
+        cpu.advance_state().unwrap();
+        assert_eq!(cpu.v[0], v0); // same random byte
+        assert_eq!(cpu.graphics, graphics);
+    }
+
+    #[test]
+    fn test_restore_mismatched_state() {
+        let mut cpu = Cpu::new();
+        let mut state = cpu.snapshot();
+        state.memory.push(0);
+        assert!(matches!(cpu.restore(&state), Err(Chip8Error::InvalidState(_))));
+
+        let platform = Platform { stack_depth: 2, ..Platform::default() };
+        let mut cpu = Cpu::with_platform(platform, Quirks::default()).unwrap();
+        let mut state = cpu.snapshot();
+        state.stack = vec![0x200; 3];
+        state.pc = 0x300;
+        assert!(matches!(cpu.restore(&state), Err(Chip8Error::InvalidState(_))));
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_machine_state_serde() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x60, 0x2A]).unwrap();
+        cpu.advance_state().unwrap();
+        let state = cpu.snapshot();
+        let json = serde_json::to_string(&state).unwrap();
+        let decoded: MachineState = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, state);
     }
 }
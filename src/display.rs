@@ -0,0 +1,11 @@
+use crate::cpu::Cpu;
+
+/// Contents of graphics memory, indexed as `[y][x]`. Non-zero pixels are set
+pub type Framebuffer = [[u8; Cpu::GFX_WIDTH]; Cpu::GFX_HEIGHT];
+
+/// A render target for the CHIP-8 framebuffer, e.g. a window, a terminal or
+/// an image file
+pub trait Display {
+    /// Draws a complete frame
+    fn draw(&mut self, graphics: &Framebuffer);
+}
@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Errors raised while loading or executing a CHIP-8 program
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The opcode at `address` is not part of the supported instruction set
+    UnknownOpcode { opcode: u16, address: u16 },
+    /// The ROM does not fit in program memory
+    RomTooLarge { size: usize, max: usize },
+    /// A subroutine call at `address` exceeded the maximum call depth
+    StackOverflow { address: u16, depth: usize },
+    /// A return at `address` was executed with an empty call stack
+    StackUnderflow { address: u16 },
+    /// The instruction at `address` tried to access memory outside the address space
+    MemoryOutOfBounds { location: usize, address: u16 },
+    /// A `Platform` describes a machine that can't run programs
+    InvalidPlatform(String),
+    /// A `MachineState` doesn't fit the machine it is being restored into
+    InvalidState(String),
+    /// A movie file is malformed or doesn't match the ROM. `line` is 0 if
+    /// the problem isn't on a particular line
+    InvalidMovie { line: usize, reason: String },
+    Io(io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { opcode, address } =>
+                write!(f, "unknown opcode {:#06x} at {:#06x}", opcode, address),
+            Chip8Error::RomTooLarge { size, max } =>
+                write!(f, "ROM is {} bytes, maximum is {} bytes", size, max),
+            Chip8Error::StackOverflow { address, depth } =>
+                write!(f, "call stack overflow at {:#06x}, more than {} nested calls", address, depth),
+            Chip8Error::StackUnderflow { address } =>
+                write!(f, "return with empty call stack at {:#06x}", address),
+            Chip8Error::MemoryOutOfBounds { location, address } =>
+                write!(f, "memory access out of bounds ({:#06x}) at {:#06x}", location, address),
+            Chip8Error::InvalidPlatform(reason) => write!(f, "invalid platform: {}", reason),
+            Chip8Error::InvalidState(reason) => write!(f, "invalid machine state: {}", reason),
+            Chip8Error::InvalidMovie { line: 0, reason } => write!(f, "invalid movie: {}", reason),
+            Chip8Error::InvalidMovie { line, reason } => write!(f, "invalid movie, line {}: {}", line, reason),
+            Chip8Error::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(err: io::Error) -> Chip8Error {
+        Chip8Error::Io(err)
+    }
+}
@@ -0,0 +1,231 @@
+use std::error::Error;
+use std::fmt;
+
+/// A decoded CHIP-8 instruction. `x` and `y` are register numbers, `nn` and
+/// `nnn` are the 8 and 12 bit immediates and `n` the 4 bit sprite height
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 00E0: clear screen
+    ClearScreen,
+    /// 00EE: return from subroutine
+    Return,
+    /// 1NNN: jump to NNN
+    Jump { nnn: u16 },
+    /// 2NNN: call subroutine at NNN
+    Call { nnn: u16 },
+    /// 3XNN: skip next instr if V[X] == NN
+    SkipEqImm { x: u8, nn: u8 },
+    /// 4XNN: skip next instr if V[X] != NN
+    SkipNeImm { x: u8, nn: u8 },
+    /// 5XY0: skip next instr if V[X] == V[Y]
+    SkipEqReg { x: u8, y: u8 },
+    /// 6XNN: V[X] = NN
+    LoadImm { x: u8, nn: u8 },
+    /// 7XNN: V[X] += NN (carry flag not changed)
+    AddImm { x: u8, nn: u8 },
+    /// 8XY0: V[X] = V[Y]
+    Move { x: u8, y: u8 },
+    /// 8XY1: V[X] = V[X] OR V[Y]
+    Or { x: u8, y: u8 },
+    /// 8XY2: V[X] = V[X] AND V[Y]
+    And { x: u8, y: u8 },
+    /// 8XY3: V[X] = V[X] XOR V[Y]
+    Xor { x: u8, y: u8 },
+    /// 8XY4: V[X] = V[X] + V[Y] (carry flag set to 1 if carry, 0 if not)
+    Add { x: u8, y: u8 },
+    /// 8XY5: V[X] = V[X] - V[Y] (carry flag set to 0 if borrow, 1 if not)
+    Sub { x: u8, y: u8 },
+    /// 8XY6: V[F] gets least sig bit of V[X], then V[X] >>= 1
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7: V[X] = V[Y] - V[X] (carry flag set to 0 if borrow, 1 if not)
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE: V[F] gets most sig bit of V[X], then V[X] <<= 1
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0: skip next instr if V[X] != V[Y]
+    SkipNeReg { x: u8, y: u8 },
+    /// ANNN: Index = NNN
+    LoadIndex { nnn: u16 },
+    /// BNNN: PC = V[0] + NNN. `x` is the high nibble of NNN, used by the
+    /// jump-with-VX quirk
+    JumpOffset { x: u8, nnn: u16 },
+    /// CXNN: V[X] = rand() AND NN
+    Random { x: u8, nn: u8 },
+    /// DXYN: draw sprite at (V[X], V[Y]), 8px wide x N high
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E: skip next instr if key V[X] is pressed
+    SkipKeyPressed { x: u8 },
+    /// EXA1: skip next instr if key V[X] is NOT pressed
+    SkipKeyNotPressed { x: u8 },
+    /// FX07: V[X] = value of delay timer
+    LoadDelay { x: u8 },
+    /// FX0A: V[X] = key press (blocking wait for key press)
+    WaitKey { x: u8 },
+    /// FX15: delay timer = V[X]
+    SetDelay { x: u8 },
+    /// FX18: sound timer = V[X]
+    SetSound { x: u8 },
+    /// FX1E: Index += V[X]
+    AddIndex { x: u8 },
+    /// FX29: Index = location of hex sprite for the value of V[X]
+    LoadFont { x: u8 },
+    /// FX33: store the binary-coded decimal representation of V[X] at Index
+    StoreBcd { x: u8 },
+    /// FX55: store V[0] thru V[X] inclusive in memory starting at Index
+    StoreRegisters { x: u8 },
+    /// FX65: load V[0] thru V[X] inclusive from memory starting at Index
+    LoadRegisters { x: u8 },
+}
+
+/// The opcode is not part of the supported instruction set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown opcode {:#06x}", self.opcode)
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Decodes a big-endian 16 bit opcode
+pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+    let x = (opcode >> 8) as u8 & 0xF;
+    let y = (opcode >> 4) as u8 & 0xF;
+    let n = opcode as u8 & 0xF;
+    let nn = opcode as u8;
+    let nnn = opcode & 0xFFF;
+
+    let instruction = match opcode >> 12 {
+        0x0 => match opcode {
+            0x00E0 => Instruction::ClearScreen,
+            0x00EE => Instruction::Return,
+            _ => return Err(DecodeError { opcode }), // We don't support 0x0NNN instructions
+        }
+        0x1 => Instruction::Jump { nnn },
+        0x2 => Instruction::Call { nnn },
+        0x3 => Instruction::SkipEqImm { x, nn },
+        0x4 => Instruction::SkipNeImm { x, nn },
+        0x5 => Instruction::SkipEqReg { x, y },
+        0x6 => Instruction::LoadImm { x, nn },
+        0x7 => Instruction::AddImm { x, nn },
+        0x8 => match n {
+            0x0 => Instruction::Move { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::Add { x, y },
+            0x5 => Instruction::Sub { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubReverse { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => return Err(DecodeError { opcode }),
+        }
+        0x9 => Instruction::SkipNeReg { x, y },
+        0xA => Instruction::LoadIndex { nnn },
+        0xB => Instruction::JumpOffset { x, nnn },
+        0xC => Instruction::Random { x, nn },
+        0xD => Instruction::Draw { x, y, n },
+        0xE => match nn {
+            0x9E => Instruction::SkipKeyPressed { x },
+            0xA1 => Instruction::SkipKeyNotPressed { x },
+            _ => return Err(DecodeError { opcode }),
+        }
+        0xF => match nn {
+            0x07 => Instruction::LoadDelay { x },
+            0x0A => Instruction::WaitKey { x },
+            0x15 => Instruction::SetDelay { x },
+            0x18 => Instruction::SetSound { x },
+            0x1E => Instruction::AddIndex { x },
+            0x29 => Instruction::LoadFont { x },
+            0x33 => Instruction::StoreBcd { x },
+            0x55 => Instruction::StoreRegisters { x },
+            0x65 => Instruction::LoadRegisters { x },
+            _ => return Err(DecodeError { opcode }),
+        }
+        _ => return Err(DecodeError { opcode }),
+    };
+    Ok(instruction)
+}
+
+/// Disassembles the instruction using the common Cowgod mnemonics
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::Jump { nnn } => write!(f, "JP {:#05x}", nnn),
+            Instruction::Call { nnn } => write!(f, "CALL {:#05x}", nnn),
+            Instruction::SkipEqImm { x, nn } => write!(f, "SE V{:X}, {:#04x}", x, nn),
+            Instruction::SkipNeImm { x, nn } => write!(f, "SNE V{:X}, {:#04x}", x, nn),
+            Instruction::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LoadImm { x, nn } => write!(f, "LD V{:X}, {:#04x}", x, nn),
+            Instruction::AddImm { x, nn } => write!(f, "ADD V{:X}, {:#04x}", x, nn),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadIndex { nnn } => write!(f, "LD I, {:#05x}", nnn),
+            // Which register is added depends on the jump-with-VX quirk
+            Instruction::JumpOffset { x: 0, nnn } => write!(f, "JP V0, {:#05x}", nnn),
+            Instruction::JumpOffset { x, nnn } => write!(f, "JP V0/V{:X}, {:#05x}", x, nnn),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, {:#04x}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::StoreBcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegisters { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegisters { x } => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod instruction_tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x00E0), Ok(Instruction::ClearScreen));
+        assert_eq!(decode(0x00EE), Ok(Instruction::Return));
+        assert_eq!(decode(0x1ABC), Ok(Instruction::Jump { nnn: 0xABC }));
+        assert_eq!(decode(0x3122), Ok(Instruction::SkipEqImm { x: 1, nn: 0x22 }));
+        assert_eq!(decode(0x8AB6), Ok(Instruction::ShiftRight { x: 0xA, y: 0xB }));
+        assert_eq!(decode(0xB345), Ok(Instruction::JumpOffset { x: 3, nnn: 0x345 }));
+        assert_eq!(decode(0xD123), Ok(Instruction::Draw { x: 1, y: 2, n: 3 }));
+        assert_eq!(decode(0xE59E), Ok(Instruction::SkipKeyPressed { x: 5 }));
+        assert_eq!(decode(0xFC33), Ok(Instruction::StoreBcd { x: 0xC }));
+    }
+
+    #[test]
+    fn test_decode_unknown() {
+        for opcode in [0x0123, 0x8128, 0xE1A2, 0xF1FF].iter() {
+            assert_eq!(decode(*opcode), Err(DecodeError { opcode: *opcode }));
+        }
+    }
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(decode(0x00E0).unwrap().to_string(), "CLS");
+        assert_eq!(decode(0x2ABC).unwrap().to_string(), "CALL 0xabc");
+        assert_eq!(decode(0x6A0F).unwrap().to_string(), "LD VA, 0x0f");
+        assert_eq!(decode(0xD12F).unwrap().to_string(), "DRW V1, V2, 15");
+        assert_eq!(decode(0xF265).unwrap().to_string(), "LD V2, [I]");
+        assert_eq!(decode(0xB045).unwrap().to_string(), "JP V0, 0x045");
+        assert_eq!(decode(0xB345).unwrap().to_string(), "JP V0/V3, 0x345");
+    }
+}
@@ -0,0 +1,58 @@
+/// One of the 16 keys on the CHIP-8 hex keypad
+///
+/// ```text
+/// 1 2 3 C
+/// 4 5 6 D
+/// 7 8 9 E
+/// A 0 B F
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Num0 = 0x0,
+    Num1 = 0x1,
+    Num2 = 0x2,
+    Num3 = 0x3,
+    Num4 = 0x4,
+    Num5 = 0x5,
+    Num6 = 0x6,
+    Num7 = 0x7,
+    Num8 = 0x8,
+    Num9 = 0x9,
+    A    = 0xA,
+    B    = 0xB,
+    C    = 0xC,
+    D    = 0xD,
+    E    = 0xE,
+    F    = 0xF,
+}
+
+impl Key {
+    /// Every key, ordered by value
+    pub const ALL: [Key; 16] = [Key::Num0, Key::Num1, Key::Num2, Key::Num3,
+                                Key::Num4, Key::Num5, Key::Num6, Key::Num7,
+                                Key::Num8, Key::Num9, Key::A, Key::B,
+                                Key::C, Key::D, Key::E, Key::F];
+
+    /// Returns the key with the given value, or None if it is greater than 0xF
+    pub fn from_index(index: u8) -> Option<Key> {
+        Key::ALL.get(index as usize).copied()
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[cfg(test)]
+mod keypad_tests {
+    use super::*;
+
+    #[test]
+    fn test_from_index() {
+        for (i, key) in Key::ALL.iter().enumerate() {
+            assert_eq!(Key::from_index(i as u8), Some(*key));
+            assert_eq!(key.index(), i);
+        }
+        assert_eq!(Key::from_index(0x10), None);
+    }
+}
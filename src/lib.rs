@@ -0,0 +1,37 @@
+//! CHIP-8 interpreter core.
+//!
+//! The `Cpu` type holds the complete machine (registers, memory, framebuffer,
+//! keypad and timers) and can be driven by any front end: load a ROM, call
+//! `advance_state` to execute instructions and `decrement_timers` at 60Hz,
+//! then hand the framebuffer to a `Display` implementation.
+//! Faults such as unknown opcodes are reported as a `Chip8Error`.
+
+pub mod cpu;
+pub mod display;
+pub mod error;
+pub mod instruction;
+pub mod keypad;
+pub mod memory;
+pub mod movie;
+pub mod phosphor;
+pub mod platform;
+pub mod quirks;
+pub mod rewind;
+pub mod rng;
+pub mod stack;
+pub mod state;
+
+pub use crate::cpu::Cpu;
+pub use crate::display::Display;
+pub use crate::error::Chip8Error;
+pub use crate::instruction::{decode, Instruction};
+pub use crate::keypad::Key;
+pub use crate::memory::{AddressPolicy, Memory};
+pub use crate::movie::{InputEvent, Movie};
+pub use crate::phosphor::Phosphor;
+pub use crate::platform::Platform;
+pub use crate::quirks::Quirks;
+pub use crate::rewind::Rewind;
+pub use crate::rng::{RandomSource, SeededRng};
+pub use crate::stack::Stack;
+pub use crate::state::MachineState;
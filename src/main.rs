@@ -1,11 +1,17 @@
-mod cpu;
-use crate::cpu::Cpu;
+mod sdl;
+
+use chip8::{Chip8Error, Cpu, Display, Movie, Quirks, Rewind, SeededRng};
+use crate::sdl::controller::ButtonBindings;
+use crate::sdl::input::{Hotkey, KeyBindings};
+use crate::sdl::{ConfigFile, Controllers, SaveSlots, SdlAudio, SdlDisplay, Settings};
+use std::convert::TryFrom;
 use std::env;
+use std::fs;
+use std::path::PathBuf;
 use std::thread::sleep;
 use std::time::{Instant, Duration};
 
-extern crate sdl2;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::pixels::Color;
 
 pub struct Config {
@@ -13,74 +19,441 @@ pub struct Config {
     pub scale: u32, // How big to make a single pixel
     pub black: Color, // The color of an "activated" pixel
     pub white: Color, // The color of an "inactive" pixel
+    pub border: Color, // The color around the display when the window doesn't fit it exactly
+    pub phosphor: f32, // How slowly cleared pixels fade out, 0.0 for not at all
+    pub ipf: u32, // Instructions executed per 60Hz frame
+    pub quirks: Quirks, // Behaviour of the interpreter being emulated
+    pub seed: Option<u64>, // Seed for CXNN, random if not given
+    pub fullscreen: bool, // Start in fullscreen mode
+    pub rewind_seconds: u32, // How far back the rewind key can go
+    pub record: Option<String>, // Movie file to record keypad input to
+    pub play: Option<String>, // Movie file to replay keypad input from
+    pub tone: f32, // Frequency of the buzzer in Hz
+    pub volume: f32, // Volume of the buzzer, 0.0 to 1.0
+    pub mute: bool, // Start with the sound muted
+    pub config_file: Option<PathBuf>, // Config file to use instead of the default one
+    pub keys: KeyBindings, // Keyboard layout of the keypad
+    pub buttons: ButtonBindings, // Game controller layout of the keypad
 }
 
-fn process_args(args: &[String]) -> Config {
-    if args.len() != 2 {
-        println!("Usage: {} <rom file>", args[0]);
-        std::process::exit(1);
+const USAGE: &str = "Usage: chip8-rust <rom file> [options]
+
+Options:
+    --scale <n>         size of a CHIP-8 pixel in screen pixels (default 10)
+    --fg <rrggbb>       colour of lit pixels as a hex triplet (default 5a3921)
+    --bg <rrggbb>       colour of unlit pixels as a hex triplet (default ffffb5)
+    --border <rrggbb>   colour around the display in a resized window (default 2d1c10)
+    --phosphor <decay>  fade cleared pixels out over several frames to reduce
+                        flicker, keeping this fraction of their brightness
+                        each frame (0.0 to 0.99, default 0.0 for off)
+    --ips <n>           instructions executed per second (default 600)
+    --quirks <name>     interpreter behaviour: default, vip, chip48 or schip
+    --seed <n>          seed for the random number generator
+    --fullscreen        start in fullscreen mode
+    --mute              start with the sound muted
+    --record <file>     record keypad input to a movie file
+    --play <file>       replay keypad input from a movie file
+    --config <file>     read settings from this file instead of config.toml
+                        in the config directory
+    -h, --help          show this message";
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rom: String::new(),
+            scale: 10,
+            black: Color::RGB(0x5a, 0x39, 0x21),
+            white: Color::RGB(0xff, 0xff, 0xb5),
+            border: Color::RGB(0x2d, 0x1c, 0x10),
+            phosphor: 0.0,
+            ipf: Cpu::DEFAULT_INSTRUCTIONS_PER_FRAME,
+            quirks: Quirks::default(),
+            seed: None,
+            fullscreen: false,
+            rewind_seconds: 30,
+            record: None,
+            play: None,
+            tone: 440.0,
+            volume: 0.25,
+            mute: false,
+            config_file: None,
+            keys: KeyBindings::default(),
+            buttons: ButtonBindings::default(),
+        }
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("chip8-rust: {}\n{}", msg, USAGE);
+    std::process::exit(1);
+}
+
+fn option_value<'a>(flag: &str, value: Option<&'a String>) -> &'a str {
+    match value {
+        Some(value) => value,
+        None => usage_error(&format!("{} expects a value", flag)),
     }
+}
+
+fn parse_number(flag: &str, value: Option<&String>) -> u64 {
+    let value = option_value(flag, value);
+    match value.parse::<u64>() {
+        Ok(n) => n,
+        Err(_) => usage_error(&format!("{} expects a non-negative integer, got \"{}\"", flag, value)),
+    }
+}
 
-    let fname = &args[1];
+/// Parses the command line into the options only it can set, and settings
+/// that override the config file
+fn process_args(args: &[String]) -> (Config, Settings) {
+    let mut config = Config::default();
+    let mut settings = Settings::default();
+    let mut rom = None;
 
-    Config {
-        rom: fname.to_string(),
-        scale: 10,
-        black: Color::RGB(0x5a, 0x39, 0x21),
-        white: Color::RGB(0xff, 0xff, 0xb5),
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" => {
+                settings.scale = match u32::try_from(parse_number(arg, args.next())) {
+                    Ok(scale) => Some(scale),
+                    Err(_) => usage_error("--scale must be between 1 and 100"),
+                };
+            },
+            "--fg" => settings.fg = Some(option_value(arg, args.next()).to_string()),
+            "--bg" => settings.bg = Some(option_value(arg, args.next()).to_string()),
+            "--border" => settings.border = Some(option_value(arg, args.next()).to_string()),
+            "--phosphor" => {
+                let value = option_value(arg, args.next());
+                settings.phosphor = match value.parse::<f32>() {
+                    Ok(decay) => Some(decay),
+                    Err(_) => usage_error(&format!("--phosphor expects a number such as 0.6, got \"{}\"", value)),
+                };
+            },
+            "--ips" => settings.ips = Some(parse_number(arg, args.next())),
+            "--quirks" => settings.quirks = Some(option_value(arg, args.next()).to_string()),
+            "--seed" => config.seed = Some(parse_number(arg, args.next())),
+            "--fullscreen" => settings.fullscreen = Some(true),
+            "--mute" => settings.mute = Some(true),
+            "--config" => config.config_file = Some(PathBuf::from(option_value(arg, args.next()))),
+            "--record" => config.record = Some(option_value(arg, args.next()).to_string()),
+            "--play" => config.play = Some(option_value(arg, args.next()).to_string()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("unknown option {}", arg)),
+            _ if rom.is_none() => rom = Some(arg.to_string()),
+            _ => usage_error("only one ROM file may be given"),
+        }
+    }
+
+    // Check the values now, so that mistakes are reported as usage errors
+    if let Err(err) = settings.apply(&mut Config::default()) {
+        usage_error(&format!("--{}", err));
+    }
+    if config.record.is_some() && config.play.is_some() {
+        usage_error("--record and --play can't be used together");
     }
+    match rom {
+        Some(rom) => config.rom = rom,
+        None => usage_error("no ROM file given"),
+    }
+    (config, settings)
 }
 
+#[cfg(not(tarpaulin_include))]
+fn run_hotkey(hotkey: Hotkey, cpu: &mut Cpu, save_slots: Option<&SaveSlots>, audio: Option<&mut SdlAudio>) {
+    if hotkey == Hotkey::ToggleMute {
+        if let Some(audio) = audio {
+            println!("chip8: sound {}", if audio.toggle_mute() { "muted" } else { "on" });
+        }
+        return;
+    }
+    let slots = match save_slots {
+        Some(slots) => slots,
+        None => {
+            println!("chip8: no data directory for save states, set $HOME or $XDG_DATA_HOME");
+            return;
+        },
+    };
+    match hotkey {
+        Hotkey::SaveState(slot) => match slots.save(slot, cpu) {
+            Ok(path) => println!("chip8: saved slot {} to {}", slot, path.display()),
+            Err(err) => println!("chip8: could not save slot {}: {}", slot, err),
+        },
+        Hotkey::LoadState(slot) => match slots.load(slot, cpu) {
+            Ok(()) => println!("chip8: loaded slot {}", slot),
+            Err(err) => println!("chip8: could not load slot {}: {}", slot, err),
+        },
+        _ => (),
+    }
+}
 
 #[cfg(not(tarpaulin_include))]
 fn main() {
-    let mut cpu = Cpu::new();
-    let args: Vec<String> = env::args().collect(); 
-    let config = process_args(&args);
+    let args: Vec<String> = env::args().collect();
+    let (mut config, cli_settings) = process_args(&args);
+
+    let rom = match fs::read(&config.rom) {
+        Ok(rom) => rom,
+        Err(err) => {
+            println!("chip8: error loading ROM file: \"{}\": {}", config.rom, err);
+            std::process::exit(1);
+        },
+    };
+
+    // Built in defaults, then the config file's defaults and the ROM's own
+    // section, then the command line
+    let config_path = config.config_file.clone().or_else(ConfigFile::default_path);
+    if let Some(path) = config_path {
+        let file_settings = ConfigFile::load(&path)
+            .map(|file| file.settings_for(&sdl::paths::rom_id(&rom)))
+            .and_then(|settings| settings.apply(&mut config));
+        if let Err(err) = file_settings {
+            eprintln!("chip8-rust: {}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+    if let Err(err) = cli_settings.apply(&mut config) {
+        usage_error(&format!("--{}", err));
+    }
+
+    let mut cpu = Cpu::with_quirks(config.quirks);
     let window_height = Cpu::GFX_HEIGHT as u32 * config.scale;
     let window_width = Cpu::GFX_WIDTH as u32 * config.scale;
-
-    if cpu.load_rom(config.rom.as_str()).is_err() {
-        println!("chip8: error loading ROM file: \"{}\"", config.rom);
+    if let Err(err) = cpu.load_rom_bytes(&rom) {
+        println!("chip8: error loading ROM file: \"{}\": {}", config.rom, err);
         std::process::exit(1);
     }
+    let save_slots = sdl::paths::data_dir()
+        .map(|dir| SaveSlots::new(dir.join("states"), sdl::paths::rom_id(&rom)));
+    cpu.set_instructions_per_frame(config.ipf);
+    if let Some(seed) = config.seed {
+        cpu.set_seed(seed);
+    }
+
+    // Movies replay exactly only with the seed and speed they were recorded
+    // with, so both are always pinned when one is involved
+    let mut playback = config.play.as_ref().map(|path| {
+        let movie = fs::read_to_string(path).map_err(Chip8Error::from)
+            .and_then(|text| Movie::parse(&text))
+            .and_then(|movie| movie.check_rom(&rom).map(|()| movie));
+        match movie {
+            Ok(movie) => movie,
+            Err(err) => {
+                println!("chip8: error loading movie file: \"{}\": {}", path, err);
+                std::process::exit(1);
+            },
+        }
+    });
+    let mut recording = config.record.as_ref().map(|_| Movie {
+        rom: Some(sdl::paths::rom_id(&rom)),
+        seed: Some(config.seed.unwrap_or_else(|| SeededRng::from_entropy().next_u64())),
+        ipf: Some(config.ipf),
+        quirks: Some(config.quirks),
+        events: Vec::new(),
+    });
+    if let Some(movie) = playback.as_ref().or(recording.as_ref()) {
+        if let Some(seed) = movie.seed {
+            cpu.set_seed(seed);
+        }
+        if let Some(ipf) = movie.ipf {
+            cpu.set_instructions_per_frame(ipf);
+        }
+        if let Some(quirks) = movie.quirks {
+            cpu.set_quirks(quirks);
+        }
+    }
 
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem.window("Chip-8 Rust", window_width, window_height)
-        .position_centered()
-        .build()
-        .unwrap();
+    let mut window_builder = video_subsystem.window("Chip-8 Rust", window_width, window_height);
+    window_builder.position_centered().resizable();
+    if config.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    let mut window = window_builder.build().unwrap();
+    if let Err(err) = window.set_minimum_size(Cpu::GFX_WIDTH as u32, Cpu::GFX_HEIGHT as u32) {
+        println!("chip8: {}", err);
+    }
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    let mut canvas = window.into_canvas().build().unwrap();
+    let mut controllers = match sdl_context.game_controller() {
+        Ok(subsystem) => Some(Controllers::new(subsystem)),
+        Err(err) => {
+            println!("chip8: no game controller support: {}", err);
+            None
+        },
+    };
+
+    let mut audio = match sdl_context.audio()
+        .and_then(|audio| SdlAudio::new(&audio, config.tone, config.volume, config.mute)) {
+        Ok(audio) => Some(audio),
+        Err(err) => {
+            println!("chip8: no sound: {}", err);
+            None
+        },
+    };
+
+    let canvas = window.into_canvas().build().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut display = SdlDisplay::new(canvas, &texture_creator, &config).unwrap();
+    display.draw(cpu.graphics());
     // Game loop
 
-    let mut start_time = Instant::now();
-    const DELTA: Duration = Duration::from_millis(16);
+    let mut rewind = Rewind::new(config.rewind_seconds.saturating_mul(Cpu::FRAME_RATE) as usize);
+    let mut rewinding = false;
+    let mut frame: u64 = 0;
+    let mut paused = false;
+    let mut advance = false; // Run one frame even though paused
+
+    let frame_time = Duration::from_secs(1) / Cpu::FRAME_RATE;
+    let mut next_frame = Instant::now();
     'gameloop: loop {
-        cpu.advance_state();
+        // Jumping around in time would desynchronise a movie
+        let movie_active = recording.is_some() || playback.is_some();
         for event in event_pump.poll_iter() {
+            let hotkey = match event {
+                Event::KeyDown {keycode: Some(keycode), keymod, ..} |
+                Event::KeyUp {keycode: Some(keycode), keymod, ..} => sdl::input::hotkey(keycode, keymod),
+                _ => None,
+            };
+            let blocked = movie_active && matches!(hotkey,
+                Some(Hotkey::Rewind) | Some(Hotkey::LoadState(_)) | Some(Hotkey::Step) | Some(Hotkey::Reset));
             match event {
                 Event::Quit {..} => {
                     break 'gameloop;
                 },
+                Event::KeyDown {repeat: false, ..} if blocked => {
+                    println!("chip8: rewinding, loading states, stepping and resetting are disabled \
+                              while a movie is recorded or played");
+                },
+                // Swallow key repeats too, or holding one of these would slip through
+                Event::KeyDown {..} if blocked => {},
+                Event::KeyDown {repeat: false, ..} if hotkey == Some(Hotkey::ToggleFullscreen) => {
+                    match display.toggle_fullscreen() {
+                        Ok(_) => display.redraw(),
+                        Err(err) => println!("chip8: could not switch to fullscreen: {}", err),
+                    }
+                },
+                Event::KeyDown {repeat, ..} if hotkey.is_some() => match hotkey {
+                    Some(Hotkey::Rewind) => rewinding = !movie_active,
+                    Some(Hotkey::Quit) => break 'gameloop,
+                    Some(Hotkey::Pause) if !repeat => {
+                        paused = !paused;
+                        println!("chip8: {}", if paused { "paused" } else { "resumed" });
+                    },
+                    // Held down, these keep stepping at the key repeat rate
+                    Some(Hotkey::FrameAdvance) => {
+                        paused = true;
+                        advance = true;
+                    },
+                    Some(Hotkey::Step) => {
+                        paused = true;
+                        match cpu.peek_instruction() {
+                            Ok(instruction) => println!("chip8: {:#06x}: {}", cpu.pc(), instruction),
+                            Err(err) => println!("chip8: {}", err),
+                        }
+                        if let Err(err) = cpu.step() {
+                            println!("chip8: {}", err);
+                        }
+                    },
+                    Some(Hotkey::Reset) if !repeat => {
+                        // Keeps the random number generator, unlike a restart
+                        if let Err(err) = cpu.load_rom_bytes(&rom) {
+                            println!("chip8: {}", err);
+                            break 'gameloop;
+                        }
+                        rewind.clear();
+                        println!("chip8: reset");
+                    },
+                    Some(hotkey) if !repeat => run_hotkey(hotkey, &mut cpu, save_slots.as_ref(), audio.as_mut()),
+                    _ => (),
+                },
+                Event::KeyUp {..} if hotkey == Some(Hotkey::Rewind) => {
+                    rewinding = false;
+                },
+                Event::Window {win_event: WindowEvent::SizeChanged(..), ..} |
+                Event::Window {win_event: WindowEvent::Exposed, ..} => {
+                    display.redraw();
+                },
+                Event::ControllerDeviceAdded {..} |
+                Event::ControllerDeviceRemoved {..} => {
+                    let released = match controllers.as_mut() {
+                        Some(controllers) => controllers.handle_event(&config.buttons, &event),
+                        None => Vec::new(),
+                    };
+                    // Keys held on an unplugged controller would otherwise stay pressed
+                    if playback.is_none() {
+                        for key in released {
+                            cpu.release_button(key);
+                            if let Some(movie) = recording.as_mut() {
+                                movie.record(frame, key, false);
+                            }
+                        }
+                    }
+                },
                 Event::KeyDown {..} |
-                Event::KeyUp {..} => {
-                    cpu.process_input(event);
+                Event::KeyUp {..} |
+                Event::ControllerButtonDown {..} |
+                Event::ControllerButtonUp {..} if playback.is_none() => {
+                    if let Some(controllers) = controllers.as_mut() {
+                        controllers.handle_event(&config.buttons, &event);
+                    }
+                    let input = sdl::input::process_input(&mut cpu, &config.keys, &event)
+                        .or_else(|| sdl::controller::process_input(&mut cpu, &config.buttons, &event));
+                    if let (Some((key, pressed)), Some(movie)) = (input, recording.as_mut()) {
+                        movie.record(frame, key, pressed);
+                    }
                 },
                 _ => (),
             }
         }
-        sleep(Duration::from_millis(1));
-        let new_time = Instant::now();
+        if rewinding {
+            rewind.step_back(&mut cpu);
+        } else if !paused || advance {
+            advance = false;
+            if let Some(movie) = &playback {
+                movie.apply(frame, &mut cpu);
+                if frame + 1 >= movie.len_frames() {
+                    println!("chip8: movie finished after {} frames", frame + 1);
+                    playback = None;
+                }
+            }
+            if let Err(err) = cpu.run_frame() {
+                println!("chip8: {}", err);
+                break 'gameloop;
+            }
+            frame += 1;
+            rewind.record(&cpu);
+        }
+        if display.animated() {
+            display.draw(cpu.graphics());
+            cpu.mark_drawn();
+        } else {
+            cpu.update_graphics(&mut display);
+        }
+        if let Some(audio) = audio.as_mut() {
+            audio.update(cpu.sound_active() && !rewinding && !paused);
+        }
 
-        if new_time.duration_since(start_time) >= DELTA {
-            cpu.decrement_timers();
-            start_time = Instant::now();
+        // Sleep until the next frame is due. If we fell behind (e.g. the
+        // window was being dragged) start counting again from now rather
+        // than running a burst of frames to catch up
+        next_frame += frame_time;
+        let now = Instant::now();
+        if next_frame > now {
+            sleep(next_frame - now);
+        } else {
+            next_frame = now;
         }
-        cpu.update_graphics(&config, &mut canvas);
     }
 
+    if let (Some(path), Some(movie)) = (&config.record, &recording) {
+        match fs::write(path, movie.to_string()) {
+            Ok(()) => println!("chip8: recorded {} frames to {}", frame, path),
+            Err(err) => println!("chip8: could not write movie file: \"{}\": {}", path, err),
+        }
+    }
 }
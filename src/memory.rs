@@ -0,0 +1,126 @@
+use std::ops::{Deref, DerefMut};
+
+/// What happens when an instruction accesses an address past the end of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressPolicy {
+    /// The address wraps around to the start of memory, like the address
+    /// lines of the original hardware
+    Wrap,
+    /// The access fails with `Chip8Error::MemoryOutOfBounds`
+    Error,
+}
+
+/// The interpreter's RAM. Dereferences to a byte slice for direct access;
+/// `read` and `write` apply the address policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory {
+    bytes: Vec<u8>,
+    policy: AddressPolicy,
+}
+
+impl Memory {
+    /// 4 KiB, as on the COSMAC VIP and most later interpreters
+    pub const DEFAULT_SIZE: usize = 0x1000;
+    /// The largest address space reachable with a 16 bit index register
+    pub const MAX_SIZE: usize = 0x10000;
+
+    /// Creates zeroed memory. Panics if `size` is 0 or greater than `MAX_SIZE`
+    pub fn new(size: usize, policy: AddressPolicy) -> Memory {
+        assert!(size > 0 && size <= Memory::MAX_SIZE,
+                "memory size must be between 1 and {:#x} bytes, got {:#x}", Memory::MAX_SIZE, size);
+        Memory { bytes: vec![0; size], policy }
+    }
+
+    pub fn policy(&self) -> AddressPolicy {
+        self.policy
+    }
+
+    /// Resolves `location` according to the address policy, or None if it is
+    /// out of bounds
+    fn resolve(&self, location: usize) -> Option<usize> {
+        match self.policy {
+            AddressPolicy::Wrap => Some(location % self.bytes.len()),
+            AddressPolicy::Error if location < self.bytes.len() => Some(location),
+            AddressPolicy::Error => None,
+        }
+    }
+
+    pub fn read(&self, location: usize) -> Option<u8> {
+        self.resolve(location).map(|location| self.bytes[location])
+    }
+
+    /// Stores `byte` at `location`. Returns false if it is out of bounds
+    pub fn write(&mut self, location: usize, byte: u8) -> bool {
+        match self.resolve(location) {
+            Some(location) => { self.bytes[location] = byte; true },
+            None => false,
+        }
+    }
+
+    /// Zeroes the contents, keeping the size and policy
+    pub fn clear(&mut self) {
+        for byte in self.bytes.iter_mut() {
+            *byte = 0;
+        }
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory::new(Memory::DEFAULT_SIZE, AddressPolicy::Error)
+    }
+}
+
+impl Deref for Memory {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl DerefMut for Memory {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+
+    #[test]
+    fn test_default_size() {
+        let memory = Memory::default();
+        assert_eq!(memory.len(), 4096);
+        assert_eq!(memory.read(0xFFF), Some(0)); // last byte is addressable
+        assert_eq!(memory.read(0x1000), None);
+    }
+
+    #[test]
+    fn test_wrap_policy() {
+        let mut memory = Memory::new(0x1000, AddressPolicy::Wrap);
+        assert!(memory.write(0x1001, 0xAB));
+        assert_eq!(memory[0x001], 0xAB);
+        assert_eq!(memory.read(0x2001), Some(0xAB));
+    }
+
+    #[test]
+    fn test_error_policy() {
+        let mut memory = Memory::new(0x1000, AddressPolicy::Error);
+        assert!(!memory.write(0x1000, 0xAB));
+        assert!(memory.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_max_size() {
+        let memory = Memory::new(Memory::MAX_SIZE, AddressPolicy::Error);
+        assert_eq!(memory.read(0xFFFF), Some(0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_large() {
+        Memory::new(Memory::MAX_SIZE + 1, AddressPolicy::Error);
+    }
+}
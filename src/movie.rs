@@ -0,0 +1,211 @@
+use crate::cpu::Cpu;
+use crate::error::Chip8Error;
+use crate::keypad::Key;
+use crate::quirks::Quirks;
+use std::fmt;
+
+/// A key going down or up at the start of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputEvent {
+    /// Frames are counted from 0, the first frame after the ROM is loaded
+    pub frame: u64,
+    pub key: Key,
+    pub pressed: bool,
+}
+
+/// A recording of the keypad input of a run. Replaying it against the same
+/// ROM with the same seed, speed and quirks reproduces the run exactly.
+///
+/// Movies are stored as text, one item per line. Blank lines and lines
+/// starting with `#` are ignored and every header line is optional, so a
+/// plain list of events is a valid movie:
+///
+/// ```text
+/// chip8-movie 1
+/// rom 2aae6c35c94fcfb415dbe95f408b9ce91ee846ed
+/// seed 1234
+/// ipf 10
+/// quirks vip
+/// 30 press 5
+/// 42 release 5
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Movie {
+    /// SHA-1 of the ROM it was recorded with, see `rom_hash`
+    pub rom: Option<String>,
+    /// Seed of the random number generator
+    pub seed: Option<u64>,
+    /// Instructions executed per frame
+    pub ipf: Option<u32>,
+    /// Interpreter behaviour, stored by preset name
+    pub quirks: Option<Quirks>,
+    /// Key presses and releases, ordered by frame
+    pub events: Vec<InputEvent>,
+}
+
+/// Hex encoded SHA-1 of a ROM image, used to check a movie is replayed
+/// against the ROM it was recorded with
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+impl Movie {
+    /// Bumped whenever the file format changes
+    pub const FORMAT_VERSION: u32 = 1;
+
+    pub fn new() -> Movie {
+        Movie::default()
+    }
+
+    /// Appends an event. Events must be recorded in frame order
+    pub fn record(&mut self, frame: u64, key: Key, pressed: bool) {
+        debug_assert!(!matches!(self.events.last(), Some(last) if last.frame > frame));
+        self.events.push(InputEvent { frame, key, pressed });
+    }
+
+    /// The frame after the last event, 0 for an empty movie
+    pub fn len_frames(&self) -> u64 {
+        self.events.last().map_or(0, |event| event.frame + 1)
+    }
+
+    /// Presses and releases the keys recorded for `frame`. Call it once per
+    /// frame, before the frame is run
+    pub fn apply(&self, frame: u64, cpu: &mut Cpu) {
+        let start = self.events.partition_point(|event| event.frame < frame);
+        for event in self.events[start..].iter().take_while(|event| event.frame == frame) {
+            if event.pressed {
+                cpu.press_button(event.key);
+            } else {
+                cpu.release_button(event.key);
+            }
+        }
+    }
+
+    /// Checks the movie was recorded with `rom`. Movies without a ROM hash
+    /// match any ROM
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        match &self.rom {
+            Some(hash) if !hash.eq_ignore_ascii_case(&rom_hash(rom)) =>
+                Err(Chip8Error::InvalidMovie { line: 0, reason: String::from("recorded with a different ROM") }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses a movie file. Events are sorted by frame, keeping the order of
+    /// events within a frame
+    pub fn parse(text: &str) -> Result<Movie, Chip8Error> {
+        let mut movie = Movie::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason: &str| Chip8Error::InvalidMovie { line: lineno + 1, reason: reason.to_string() };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["chip8-movie", version] => match version.parse::<u32>() {
+                    Ok(Movie::FORMAT_VERSION) => (),
+                    _ => return Err(error(&format!("unsupported movie version {}", version))),
+                },
+                ["rom", hash] => movie.rom = Some(hash.to_string()),
+                ["seed", seed] => movie.seed = Some(seed.parse().map_err(|_| error("bad seed"))?),
+                ["ipf", ipf] => movie.ipf = Some(ipf.parse().map_err(|_| error("bad ipf"))?),
+                ["quirks", name] => movie.quirks = Some(Quirks::preset(name).ok_or_else(|| error("unknown quirks preset"))?),
+                [frame, action, key] => {
+                    let frame = frame.parse::<u64>().ok();
+                    let pressed = match *action {
+                        "press" => Some(true),
+                        "release" => Some(false),
+                        _ => None,
+                    };
+                    let key = u8::from_str_radix(key, 16).ok().and_then(Key::from_index);
+                    match (frame, pressed, key) {
+                        (Some(frame), Some(pressed), Some(key)) =>
+                            movie.events.push(InputEvent { frame, key, pressed }),
+                        _ => return Err(error("expected \"<frame> press|release <key>\"")),
+                    }
+                },
+                _ => return Err(error("expected \"<frame> press|release <key>\"")),
+            }
+        }
+        movie.events.sort_by_key(|event| event.frame);
+        Ok(movie)
+    }
+}
+
+impl fmt::Display for Movie {
+    /// Writes the movie in the format read by `parse`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "chip8-movie {}", Movie::FORMAT_VERSION)?;
+        if let Some(rom) = &self.rom {
+            writeln!(f, "rom {}", rom)?;
+        }
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed {}", seed)?;
+        }
+        if let Some(ipf) = self.ipf {
+            writeln!(f, "ipf {}", ipf)?;
+        }
+        // Only presets can be written, which is all the front ends offer
+        if let Some(name) = self.quirks.and_then(Quirks::name) {
+            writeln!(f, "quirks {}", name)?;
+        }
+        for event in self.events.iter() {
+            let action = if event.pressed { "press" } else { "release" };
+            writeln!(f, "{} {} {:x}", event.frame, action, event.key.index())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod movie_tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut movie = Movie {
+            rom: Some(rom_hash(b"rom")),
+            seed: Some(99),
+            ipf: Some(12),
+            quirks: Some(Quirks::CHIP_48),
+            events: Vec::new(),
+        };
+        movie.record(3, Key::Num5, true);
+        movie.record(3, Key::A, true);
+        movie.record(7, Key::Num5, false);
+        let text = movie.to_string();
+        assert!(text.starts_with("chip8-movie 1\nrom "));
+        assert!(text.contains("\nquirks chip48\n"));
+        assert!(text.ends_with("3 press 5\n3 press a\n7 release 5\n"));
+        assert_eq!(Movie::parse(&text).unwrap(), movie);
+        assert_eq!(movie.len_frames(), 8);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(Movie::parse("chip8-movie 2"), Err(Chip8Error::InvalidMovie { line: 1, .. })));
+        assert!(matches!(Movie::parse("# seed\nseed x"), Err(Chip8Error::InvalidMovie { line: 2, .. })));
+        assert!(Movie::parse("1 press 5 6").is_err());
+        assert!(matches!(Movie::parse("quirks xochip"), Err(Chip8Error::InvalidMovie { line: 1, .. })));
+        assert_eq!(Movie::parse("quirks VIP").unwrap().quirks, Some(Quirks::COSMAC_VIP));
+    }
+
+    #[test]
+    fn test_apply_and_check_rom() {
+        let movie = Movie::parse("rom 3d0b5ac4e1a2df3e4a1b6f6c2c8a0d7ad5eb4c5b\n2 press 1\n2 press 2\n4 release 1").unwrap();
+        let mut cpu = Cpu::new();
+        movie.apply(1, &mut cpu);
+        assert!(!cpu.is_pressed(Key::Num1));
+        movie.apply(2, &mut cpu);
+        assert!(cpu.is_pressed(Key::Num1) && cpu.is_pressed(Key::Num2));
+        movie.apply(4, &mut cpu);
+        assert!(!cpu.is_pressed(Key::Num1) && cpu.is_pressed(Key::Num2));
+
+        assert!(movie.check_rom(b"another rom").is_err());
+        assert!(Movie::new().check_rom(b"any rom").is_ok());
+        let movie = Movie { rom: Some(rom_hash(b"abc")), ..Movie::new() };
+        assert_eq!(movie.rom.as_deref(), Some("a9993e364706816aba3e25717850c26c9cd0d89d"));
+        assert!(movie.check_rom(b"abc").is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::cpu::Cpu;
+use crate::display::Framebuffer;
+
+/// Brightness of every pixel, indexed as `[y][x]`, from 0.0 (off) to 1.0 (lit)
+pub type Levels = [[f32; Cpu::GFX_WIDTH]; Cpu::GFX_HEIGHT];
+
+/// Emulates the afterglow of a CRT's phosphor. Lit pixels are at full
+/// brightness and fade out over the following frames once cleared, which
+/// hides the flicker of sprites being erased and redrawn with XOR
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    decay: f32,
+    levels: Levels,
+}
+
+impl Phosphor {
+    /// `decay` is the fraction of its brightness an unlit pixel keeps from one
+    /// frame to the next, clamped to 0.0 (no afterglow) to 0.99
+    pub fn new(decay: f32) -> Phosphor {
+        Phosphor {
+            decay: decay.clamp(0.0, 0.99),
+            levels: [[0.0; Cpu::GFX_WIDTH]; Cpu::GFX_HEIGHT],
+        }
+    }
+
+    pub fn decay(&self) -> f32 {
+        self.decay
+    }
+
+    /// Advances the afterglow by one frame. Call it once per 60Hz frame,
+    /// whether or not the framebuffer changed
+    pub fn update(&mut self, graphics: &Framebuffer) {
+        for (levels, pixels) in self.levels.iter_mut().zip(graphics.iter()) {
+            for (level, pixel) in levels.iter_mut().zip(pixels.iter()) {
+                *level = if *pixel != 0 { 1.0 } else { *level * self.decay };
+                // Snap to black rather than fading forever
+                if *level < 1.0 / 256.0 {
+                    *level = 0.0;
+                }
+            }
+        }
+    }
+
+    pub fn levels(&self) -> &Levels {
+        &self.levels
+    }
+}
+
+#[cfg(test)]
+mod phosphor_tests {
+    use super::*;
+
+    #[test]
+    fn test_fade() {
+        let mut phosphor = Phosphor::new(0.5);
+        let mut graphics = [[0; Cpu::GFX_WIDTH]; Cpu::GFX_HEIGHT];
+        graphics[3][7] = 1;
+        phosphor.update(&graphics);
+        assert_eq!(phosphor.levels()[3][7], 1.0);
+        assert_eq!(phosphor.levels()[3][8], 0.0);
+
+        graphics[3][7] = 0;
+        phosphor.update(&graphics);
+        assert_eq!(phosphor.levels()[3][7], 0.5);
+        phosphor.update(&graphics);
+        assert_eq!(phosphor.levels()[3][7], 0.25);
+        for _ in 0..8 {
+            phosphor.update(&graphics);
+        }
+        assert_eq!(phosphor.levels()[3][7], 0.0);
+    }
+
+    #[test]
+    fn test_no_decay() {
+        let mut phosphor = Phosphor::new(0.0);
+        let mut graphics = [[0; Cpu::GFX_WIDTH]; Cpu::GFX_HEIGHT];
+        graphics[0][0] = 1;
+        phosphor.update(&graphics);
+        graphics[0][0] = 0;
+        phosphor.update(&graphics);
+        assert_eq!(phosphor.levels()[0][0], 0.0);
+        assert_eq!(Phosphor::new(2.0).decay(), 0.99);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::cpu::Cpu;
+use crate::error::Chip8Error;
+use crate::memory::{AddressPolicy, Memory};
+use crate::stack::Stack;
+
+/// Hardware parameters of the machine being emulated. Unlike `Quirks`, these
+/// describe the machine's resources rather than how instructions behave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    /// Size of RAM in bytes, at most `Memory::MAX_SIZE`
+    pub memory_size: usize,
+    /// What happens when an instruction accesses memory past `memory_size`
+    pub address_policy: AddressPolicy,
+    /// Maximum number of nested subroutine calls
+    pub stack_depth: usize,
+    /// Where ROMs are loaded and execution starts
+    pub load_address: u16,
+}
+
+impl Platform {
+    /// The usual load address, just past the COSMAC VIP's interpreter
+    pub const DEFAULT_LOAD_ADDRESS: u16 = 0x200;
+
+    /// The ETI-660, whose ROMs are loaded at 0x600
+    pub const ETI_660: Platform = Platform {
+        memory_size: Memory::DEFAULT_SIZE,
+        address_policy: AddressPolicy::Error,
+        stack_depth: Stack::DEFAULT_DEPTH,
+        load_address: 0x600,
+    };
+
+    /// Checks the machine has room for the font and loads programs inside
+    /// its memory
+    pub fn check(&self) -> Result<(), Chip8Error> {
+        let min = Cpu::FONT.len();
+        if self.memory_size < min || self.memory_size > Memory::MAX_SIZE {
+            return Err(Chip8Error::InvalidPlatform(format!(
+                "memory size must be between {:#x} and {:#x} bytes, got {:#x}",
+                min, Memory::MAX_SIZE, self.memory_size)));
+        }
+        if usize::from(self.load_address) >= self.memory_size {
+            return Err(Chip8Error::InvalidPlatform(format!(
+                "load address {:#x} is past the end of {:#x} bytes of memory",
+                self.load_address, self.memory_size)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Platform {
+            memory_size: Memory::DEFAULT_SIZE,
+            address_policy: AddressPolicy::Error,
+            stack_depth: Stack::DEFAULT_DEPTH,
+            load_address: Platform::DEFAULT_LOAD_ADDRESS,
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/// How FX55 and FX65 leave the index register after a load or store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// I is left unchanged
+    None,
+    /// I is incremented by X
+    X,
+    /// I is incremented by X + 1, pointing just past the last register copied
+    XPlusOne,
+}
+
+/// Behaviour differences between the various CHIP-8 interpreters. ROMs are
+/// usually written for one of them and can misbehave on the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6/8XYE shift VX in place. If false, VY is copied to VX before shifting
+    pub shift: bool,
+    /// What FX55/FX65 do to the index register
+    pub load_store: IndexIncrement,
+    /// BNNN jumps to XNN + VX instead of NNN + V0
+    pub jump_with_vx: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to 0
+    pub vf_reset: bool,
+    /// DXYN clips sprites at the screen edges instead of wrapping them around
+    pub clip_sprites: bool,
+    /// DXYN waits for the next 60Hz frame before drawing, so at most one
+    /// sprite is drawn per frame
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original interpreter on the RCA COSMAC VIP
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift: false,
+        load_store: IndexIncrement::XPlusOne,
+        jump_with_vx: false,
+        vf_reset: true,
+        clip_sprites: true,
+        display_wait: true,
+    };
+
+    /// CHIP-48 on the HP-48 calculators
+    pub const CHIP_48: Quirks = Quirks {
+        shift: true,
+        load_store: IndexIncrement::X,
+        jump_with_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    /// SUPER-CHIP 1.1
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift: true,
+        load_store: IndexIncrement::None,
+        jump_with_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    /// Names accepted by `Quirks::preset`
+    pub const PRESETS: [&'static str; 4] = ["default", "vip", "chip48", "schip"];
+
+    /// Looks up a preset by name: "default", "vip", "chip48" or "schip"
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Quirks::default()),
+            "vip" | "cosmac" | "chip8" => Some(Quirks::COSMAC_VIP),
+            "chip48" => Some(Quirks::CHIP_48),
+            "schip" | "superchip" => Some(Quirks::SUPER_CHIP),
+            _ => None,
+        }
+    }
+
+    /// The name of the preset these quirks match, if any
+    pub fn name(self) -> Option<&'static str> {
+        Quirks::PRESETS.iter().copied().find(|name| Quirks::preset(name) == Some(self))
+    }
+}
+
+impl Default for Quirks {
+    /// The behaviour this interpreter has always had: shifts ignore VY, I is
+    /// unchanged by loads and stores, BNNN uses V0 and sprites wrap
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            load_store: IndexIncrement::None,
+            jump_with_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod quirks_tests {
+    use super::*;
+
+    #[test]
+    fn test_preset() {
+        assert_eq!(Quirks::preset("vip"), Some(Quirks::COSMAC_VIP));
+        assert_eq!(Quirks::preset("CHIP48"), Some(Quirks::CHIP_48));
+        assert_eq!(Quirks::preset("schip"), Some(Quirks::SUPER_CHIP));
+        assert_eq!(Quirks::preset("xochip"), None);
+        for name in Quirks::PRESETS.iter() {
+            assert!(Quirks::preset(name).is_some());
+        }
+    }
+
+    #[test]
+    fn test_name() {
+        assert_eq!(Quirks::default().name(), Some("default"));
+        assert_eq!(Quirks::COSMAC_VIP.name(), Some("vip"));
+        assert_eq!(Quirks::SUPER_CHIP.name(), Some("schip"));
+        assert_eq!(Quirks { display_wait: true, ..Quirks::SUPER_CHIP }.name(), None);
+    }
+}
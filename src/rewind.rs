@@ -0,0 +1,108 @@
+use crate::cpu::Cpu;
+use crate::keypad::Key;
+use crate::state::MachineState;
+use std::collections::VecDeque;
+
+/// Ring buffer of recent machine states, one per frame, for stepping a
+/// running program backwards. The oldest state is dropped once it is full
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    states: VecDeque<MachineState>,
+    capacity: usize,
+}
+
+impl Rewind {
+    /// Keeps up to `capacity` states, e.g. `30 * Cpu::FRAME_RATE` for 30 seconds.
+    /// The buffer grows as states are recorded rather than all at once
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind { states: VecDeque::new(), capacity }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of states that can currently be stepped back through
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    /// Records the machine's current state, normally once after every frame
+    pub fn record(&mut self, cpu: &Cpu) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        self.states.push_back(cpu.snapshot());
+    }
+
+    /// Restores the most recently recorded state and forgets it. The keypad
+    /// is left as it is, so keys aren't stuck down once rewinding stops.
+    /// Returns false if there is nothing left to rewind
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> bool {
+        let state = match self.states.pop_back() {
+            Some(state) => state,
+            None => return false,
+        };
+        let held: Vec<bool> = Key::ALL.iter().map(|key| cpu.is_pressed(*key)).collect();
+        // States recorded from this machine always fit it
+        if cpu.restore(&state).is_err() {
+            return false;
+        }
+        for (key, held) in Key::ALL.iter().zip(held) {
+            if held {
+                cpu.press_button(*key);
+            } else {
+                cpu.release_button(*key);
+            }
+        }
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
+
+#[cfg(test)]
+mod rewind_tests {
+    use super::*;
+
+    #[test]
+    fn test_step_back() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap(); // V0 += 1 forever
+        let mut rewind = Rewind::new(3);
+        for _ in 0..5 {
+            cpu.run_frame().unwrap();
+            rewind.record(&cpu);
+        }
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(cpu.registers()[0], 25);
+
+        cpu.press_button(Key::Num5);
+        assert!(rewind.step_back(&mut cpu)); // state after frame 5
+        assert!(rewind.step_back(&mut cpu));
+        assert_eq!(cpu.registers()[0], 20);
+        assert!(cpu.is_pressed(Key::Num5));
+        assert!(rewind.step_back(&mut cpu));
+        assert_eq!(cpu.registers()[0], 15);
+        assert!(!rewind.step_back(&mut cpu));
+        assert_eq!(cpu.registers()[0], 15);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cpu = Cpu::new();
+        let mut rewind = Rewind::new(0);
+        rewind.record(&cpu);
+        assert!(rewind.is_empty());
+        assert!(!rewind.step_back(&mut cpu));
+    }
+}
@@ -0,0 +1,15 @@
+//! SDL2 implementations of the front end traits
+
+pub mod audio;
+pub mod controller;
+pub mod display;
+pub mod input;
+pub mod paths;
+pub mod save_slots;
+pub mod settings;
+
+pub use self::audio::SdlAudio;
+pub use self::controller::Controllers;
+pub use self::display::SdlDisplay;
+pub use self::save_slots::SaveSlots;
+pub use self::settings::{ConfigFile, Settings};
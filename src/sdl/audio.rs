@@ -0,0 +1,63 @@
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+
+/// Square wave generator run on SDL's audio thread
+pub struct SquareWave {
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+}
+
+impl AudioCallback for SquareWave {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 { self.volume } else { -self.volume };
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+/// The CHIP-8 buzzer: a square wave that plays while the sound timer runs
+pub struct SdlAudio {
+    device: AudioDevice<SquareWave>,
+    muted: bool,
+    playing: bool,
+}
+
+impl SdlAudio {
+    /// Opens the default output device. `volume` is between 0.0 and 1.0
+    pub fn new(audio: &AudioSubsystem, frequency: f32, volume: f32, muted: bool) -> Result<SdlAudio, String> {
+        let desired = AudioSpecDesired {
+            freq: Some(44_100),
+            channels: Some(1),
+            samples: Some(512), // ~12ms of latency at 44.1kHz
+        };
+        let device = audio.open_playback(None, &desired, |spec| SquareWave {
+            phase_inc: frequency / spec.freq as f32,
+            phase: 0.0,
+            volume: volume.clamp(0.0, 1.0),
+        })?;
+        Ok(SdlAudio { device, muted, playing: false })
+    }
+
+    /// Starts or stops the tone. Call once per frame with `Cpu::sound_active`
+    pub fn update(&mut self, active: bool) {
+        let play = active && !self.muted;
+        if play != self.playing {
+            if play {
+                self.device.resume();
+            } else {
+                self.device.pause();
+            }
+            self.playing = play;
+        }
+    }
+
+    /// Mutes or unmutes the tone, returning true if it is now muted
+    pub fn toggle_mute(&mut self) -> bool {
+        self.muted = !self.muted;
+        self.muted
+    }
+}
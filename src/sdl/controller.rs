@@ -0,0 +1,125 @@
+use chip8::keypad::Key;
+use chip8::Cpu;
+use std::collections::{HashMap, HashSet};
+
+use sdl2::controller::{Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+/// Which controller buttons press which keypad keys. Several buttons may
+/// press the same keypad key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ButtonBindings {
+    buttons: HashMap<Button, Key>,
+}
+
+impl Default for ButtonBindings {
+    /// The d-pad presses 2/4/6/8, which most games use for movement, and the
+    /// face and shoulder buttons press the other common action keys
+    fn default() -> Self {
+        let buttons = [
+            (Button::DPadUp,        Key::Num2),
+            (Button::DPadLeft,      Key::Num4),
+            (Button::DPadRight,     Key::Num6),
+            (Button::DPadDown,      Key::Num8),
+            (Button::A,             Key::Num5),
+            (Button::B,             Key::Num0),
+            (Button::X,             Key::Num1),
+            (Button::Y,             Key::Num3),
+            (Button::LeftShoulder,  Key::Num7),
+            (Button::RightShoulder, Key::Num9),
+            (Button::Back,          Key::E),
+            (Button::Start,         Key::F),
+        ];
+        ButtonBindings { buttons: buttons.iter().copied().collect() }
+    }
+}
+
+impl ButtonBindings {
+    /// The keypad key pressed by `button`, if any
+    pub fn key(&self, button: Button) -> Option<Key> {
+        self.buttons.get(&button).copied()
+    }
+
+    /// Makes `buttons` the only buttons for `key`, taking them away from
+    /// whatever keypad keys they pressed before
+    pub fn bind(&mut self, key: Key, buttons: &[Button]) {
+        self.buttons.retain(|_, bound| *bound != key);
+        for button in buttons {
+            self.buttons.insert(*button, key);
+        }
+    }
+}
+
+/// Forwards controller button events for bound buttons to the keypad. Returns
+/// the key and whether it was pressed, if the event was for a bound button
+pub fn process_input(cpu: &mut Cpu, bindings: &ButtonBindings, e: &Event) -> Option<(Key, bool)> {
+    match *e {
+        Event::ControllerButtonDown {button, ..} => {
+            let key = bindings.key(button)?;
+            cpu.press_button(key);
+            Some((key, true))
+        }
+        Event::ControllerButtonUp {button, ..} => {
+            let key = bindings.key(button)?;
+            cpu.release_button(key);
+            Some((key, false))
+        }
+        _ => None,
+    }
+}
+
+/// The game controllers currently plugged in. SDL reports controllers that
+/// are already connected at startup as added, so all of them are opened here
+pub struct Controllers {
+    subsystem: GameControllerSubsystem,
+    open: HashMap<u32, GameController>,
+    held: HashMap<u32, HashSet<Button>>, // Buttons held down, by instance id
+}
+
+impl Controllers {
+    pub fn new(subsystem: GameControllerSubsystem) -> Controllers {
+        Controllers { subsystem, open: HashMap::new(), held: HashMap::new() }
+    }
+
+    /// Opens and closes controllers as they are plugged in and out, and keeps
+    /// track of which buttons are held. Returns the keypad keys to release
+    /// when a controller is unplugged with bound buttons still held, leaving
+    /// out keys that a button on another controller still holds
+    pub fn handle_event(&mut self, bindings: &ButtonBindings, e: &Event) -> Vec<Key> {
+        match *e {
+            Event::ControllerButtonDown {which, button, ..} => {
+                self.held.entry(which).or_default().insert(button);
+            },
+            Event::ControllerButtonUp {which, button, ..} => {
+                if let Some(held) = self.held.get_mut(&which) {
+                    held.remove(&button);
+                }
+            },
+            Event::ControllerDeviceAdded {which, ..} => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    println!("chip8: controller connected: {}", controller.name());
+                    self.open.insert(controller.instance_id(), controller);
+                },
+                Err(err) => println!("chip8: could not open controller {}: {}", which, err),
+            },
+            Event::ControllerDeviceRemoved {which, ..} => {
+                if let Some(controller) = self.open.remove(&which) {
+                    println!("chip8: controller disconnected: {}", controller.name());
+                }
+                if let Some(released) = self.held.remove(&which) {
+                    let pressed_by = |buttons: &HashSet<Button>| -> HashSet<Key> {
+                        buttons.iter().filter_map(|button| bindings.key(*button)).collect()
+                    };
+                    let released = pressed_by(&released);
+                    let still_held: HashSet<Key> = self.held.values().flat_map(pressed_by).collect();
+                    return Key::ALL.iter().copied()
+                        .filter(|key| released.contains(key) && !still_held.contains(key))
+                        .collect();
+                }
+            },
+            _ => (),
+        }
+        Vec::new()
+    }
+}
@@ -0,0 +1,121 @@
+use chip8::display::{Display, Framebuffer};
+use chip8::{Cpu, Phosphor};
+use crate::Config;
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+/// Draws the framebuffer onto an SDL window canvas. The framebuffer is
+/// uploaded into a streaming texture at its native size and the renderer
+/// scales it up by the largest whole number that fits the window, centred
+/// on a border. With a phosphor filter, cleared pixels fade out over several
+/// frames instead of switching off at once
+pub struct SdlDisplay<'a> {
+    canvas: Canvas<Window>,
+    texture: Texture<'a>,
+    black: Color, // The color of an "activated" pixel
+    white: Color, // The color of an "inactive" pixel
+    border: Color, // The color around the scaled framebuffer
+    phosphor: Option<Phosphor>,
+}
+
+impl<'a> SdlDisplay<'a> {
+    pub fn new(canvas: Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>,
+               cfg: &Config) -> Result<SdlDisplay<'a>, String> {
+        let texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, Cpu::GFX_WIDTH as u32, Cpu::GFX_HEIGHT as u32)
+            .map_err(|err| err.to_string())?;
+        Ok(SdlDisplay {
+            canvas,
+            texture,
+            black: cfg.black,
+            white: cfg.white,
+            border: cfg.border,
+            phosphor: if cfg.phosphor > 0.0 { Some(Phosphor::new(cfg.phosphor)) } else { None },
+        })
+    }
+
+    /// True if the picture changes from frame to frame on its own, so it must
+    /// be drawn every frame rather than only when the framebuffer changes
+    pub fn animated(&self) -> bool {
+        self.phosphor.is_some()
+    }
+
+    /// Draws the last frame again, e.g. after the window was resized
+    pub fn redraw(&mut self) {
+        if let Err(err) = self.present_texture() {
+            println!("Error drawing framebuffer: {}", err);
+        }
+        self.canvas.present();
+    }
+
+    /// Switches between a window and desktop fullscreen, returning true if
+    /// the display is now fullscreen
+    pub fn toggle_fullscreen(&mut self) -> Result<bool, String> {
+        let window = self.canvas.window_mut();
+        let fullscreen = window.fullscreen_state() == FullscreenType::Off;
+        window.set_fullscreen(if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off })?;
+        Ok(fullscreen)
+    }
+
+    fn render(&mut self, graphics: &Framebuffer) -> Result<(), String> {
+        let (black, white) = (self.black, self.white);
+        match self.phosphor.as_mut() {
+            Some(phosphor) => {
+                phosphor.update(graphics);
+                let levels = phosphor.levels();
+                self.texture.with_lock(None, |buffer, pitch| {
+                    for (row, line) in levels.iter().zip(buffer.chunks_mut(pitch)) {
+                        for (level, rgb) in row.iter().zip(line.chunks_mut(3)) {
+                            rgb.copy_from_slice(&blend(white, black, *level));
+                        }
+                    }
+                })?;
+            },
+            None => {
+                self.texture.with_lock(None, |buffer, pitch| {
+                    for (row, line) in graphics.iter().zip(buffer.chunks_mut(pitch)) {
+                        for (pixel, rgb) in row.iter().zip(line.chunks_mut(3)) {
+                            let color = if *pixel != 0 { black } else { white };
+                            rgb.copy_from_slice(&[color.r, color.g, color.b]);
+                        }
+                    }
+                })?;
+            },
+        }
+        self.present_texture()
+    }
+
+    fn present_texture(&mut self) -> Result<(), String> {
+        self.canvas.set_draw_color(self.border);
+        self.canvas.clear();
+        let (width, height) = self.canvas.output_size()?;
+        self.canvas.copy(&self.texture, None, SdlDisplay::viewport(width, height))
+    }
+
+    /// Where the framebuffer goes on a `width` by `height` output: scaled by
+    /// a whole number so every CHIP-8 pixel is the same size, and centred
+    fn viewport(width: u32, height: u32) -> Rect {
+        let (gfx_width, gfx_height) = (Cpu::GFX_WIDTH as u32, Cpu::GFX_HEIGHT as u32);
+        let scale = (width / gfx_width).min(height / gfx_height).max(1);
+        let (w, h) = (gfx_width * scale, gfx_height * scale);
+        Rect::new((width as i32 - w as i32) / 2, (height as i32 - h as i32) / 2, w, h)
+    }
+}
+
+/// Mixes `from` and `to`, `amount` of the way to `to`
+fn blend(from: Color, to: Color, amount: f32) -> [u8; 3] {
+    let mix = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * amount).round() as u8;
+    [mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b)]
+}
+
+impl Display for SdlDisplay<'_> {
+    fn draw(&mut self, graphics: &Framebuffer) {
+        if let Err(err) = self.render(graphics) {
+            println!("Error drawing framebuffer: {}", err);
+        }
+        self.canvas.present();
+    }
+}
@@ -0,0 +1,125 @@
+use chip8::keypad::Key;
+use chip8::Cpu;
+use std::collections::HashMap;
+
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+
+/// Which physical keys press which keypad keys. Several physical keys may
+/// press the same keypad key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    keys: HashMap<Keycode, Key>,
+}
+
+impl Default for KeyBindings {
+    /// Maps the left hand side of a QWERTY keyboard onto the hex keypad:
+    ///
+    /// ```text
+    /// 1 2 3 4        1 2 3 C
+    /// Q W E R   ->   4 5 6 D
+    /// A S D F        7 8 9 E
+    /// Z X C V        A 0 B F
+    /// ```
+    fn default() -> Self {
+        let keys = [
+            (Keycode::X,    Key::Num0),
+            (Keycode::Num1, Key::Num1),
+            (Keycode::Num2, Key::Num2),
+            (Keycode::Num3, Key::Num3),
+            (Keycode::Q,    Key::Num4),
+            (Keycode::W,    Key::Num5),
+            (Keycode::E,    Key::Num6),
+            (Keycode::A,    Key::Num7),
+            (Keycode::S,    Key::Num8),
+            (Keycode::D,    Key::Num9),
+            (Keycode::Z,    Key::A),
+            (Keycode::C,    Key::B),
+            (Keycode::Num4, Key::C),
+            (Keycode::R,    Key::D),
+            (Keycode::F,    Key::E),
+            (Keycode::V,    Key::F),
+        ];
+        KeyBindings { keys: keys.iter().copied().collect() }
+    }
+}
+
+impl KeyBindings {
+    /// The keypad key pressed by `keycode`, if any
+    pub fn key(&self, keycode: Keycode) -> Option<Key> {
+        self.keys.get(&keycode).copied()
+    }
+
+    /// Makes `keycodes` the only physical keys for `key`, taking them away
+    /// from whatever keypad keys they pressed before
+    pub fn bind(&mut self, key: Key, keycodes: &[Keycode]) {
+        self.keys.retain(|_, bound| *bound != key);
+        for keycode in keycodes {
+            self.keys.insert(*keycode, key);
+        }
+    }
+}
+
+/// Front end commands bound to keys outside the keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    SaveState(u8),
+    LoadState(u8),
+    /// Steps backwards one frame at a time while held
+    Rewind,
+    ToggleMute,
+    Pause,
+    /// Pauses and runs a single frame
+    FrameAdvance,
+    /// Pauses and executes a single instruction
+    Step,
+    /// Restarts the ROM from scratch
+    Reset,
+    ToggleFullscreen,
+    Quit,
+}
+
+/// F1-F4 save to slots 1-4, Shift+F1-F4 load them. Backspace rewinds, M
+/// mutes the sound, P pauses, N advances a frame, I steps an instruction, F5
+/// resets, F11 or Alt+Enter switches to fullscreen and Esc quits
+pub fn hotkey(keycode: Keycode, keymod: Mod) -> Option<Hotkey> {
+    let slot = match keycode {
+        Keycode::F11 => return Some(Hotkey::ToggleFullscreen),
+        Keycode::Return if keymod.intersects(Mod::LALTMOD | Mod::RALTMOD) => return Some(Hotkey::ToggleFullscreen),
+        Keycode::Backspace => return Some(Hotkey::Rewind),
+        Keycode::M => return Some(Hotkey::ToggleMute),
+        Keycode::P => return Some(Hotkey::Pause),
+        Keycode::N => return Some(Hotkey::FrameAdvance),
+        Keycode::I => return Some(Hotkey::Step),
+        Keycode::F5 => return Some(Hotkey::Reset),
+        Keycode::Escape => return Some(Hotkey::Quit),
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        _ => return None,
+    };
+    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+        Some(Hotkey::LoadState(slot))
+    } else {
+        Some(Hotkey::SaveState(slot))
+    }
+}
+
+/// Forwards keyboard events for bound keys to the keypad. Returns the key
+/// and whether it was pressed, if the event was for a bound key
+pub fn process_input(cpu: &mut Cpu, bindings: &KeyBindings, e: &Event) -> Option<(Key, bool)> {
+    match *e {
+        Event::KeyDown {keycode: Some(keycode), repeat: false, ..} => {
+            let key = bindings.key(keycode)?;
+            cpu.press_button(key);
+            Some((key, true))
+        }
+        Event::KeyUp {keycode: Some(keycode), ..} => {
+            let key = bindings.key(keycode)?;
+            cpu.release_button(key);
+            Some((key, false))
+        }
+        _ => None,
+    }
+}
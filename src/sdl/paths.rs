@@ -0,0 +1,30 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_NAME: &str = "chip8-rust";
+
+/// Where the config file goes: `$XDG_CONFIG_HOME/chip8-rust`, falling back to
+/// `~/.config/chip8-rust`, or `%APPDATA%\chip8-rust` on Windows. None if no
+/// home directory can be found
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join(APP_NAME))
+}
+
+/// Where save states and other generated files go: `$XDG_DATA_HOME/chip8-rust`,
+/// falling back to `~/.local/share/chip8-rust`, or `%APPDATA%\chip8-rust` on
+/// Windows. None if no home directory can be found
+pub fn data_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join(APP_NAME))
+}
+
+/// Identifies a ROM by the SHA-1 of its contents, so per-ROM files follow the
+/// ROM when it is renamed or moved
+pub fn rom_id(rom: &[u8]) -> String {
+    chip8::movie::rom_hash(rom)
+}
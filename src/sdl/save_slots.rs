@@ -0,0 +1,80 @@
+use chip8::{Chip8Error, Cpu, Key, MachineState};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Numbered save state files for one ROM, stored as
+/// `<dir>/<rom id>.<slot>.state`
+pub struct SaveSlots {
+    dir: PathBuf,
+    rom_id: String,
+}
+
+impl SaveSlots {
+    pub fn new(dir: PathBuf, rom_id: String) -> SaveSlots {
+        SaveSlots { dir, rom_id }
+    }
+
+    fn path(&self, slot: u8) -> PathBuf {
+        self.dir.join(format!("{}.{}.state", self.rom_id, slot))
+    }
+
+    /// Writes the machine's state to `slot`, replacing what was there
+    pub fn save(&self, slot: u8, cpu: &Cpu) -> Result<PathBuf, Chip8Error> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(slot);
+        // Write to a temporary file first so a failed save doesn't destroy
+        // the previous one
+        let tmp = path.with_extension("state.tmp");
+        fs::write(&tmp, cpu.snapshot().to_bytes())?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    /// Restores the machine from `slot`. Leaves it untouched if the slot is
+    /// empty or the file was written by an incompatible version. The keypad
+    /// is left as it is, so keys held when saving aren't stuck down
+    pub fn load(&self, slot: u8, cpu: &mut Cpu) -> Result<(), Chip8Error> {
+        let bytes = match fs::read(self.path(slot)) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound =>
+                return Err(Chip8Error::InvalidState(format!("slot {} is empty", slot))),
+            Err(err) => return Err(err.into()),
+        };
+        let state = MachineState::from_bytes(&bytes)?;
+        let held = cpu.keypad();
+        cpu.restore(&state)?;
+        for key in Key::ALL.iter() {
+            if held[key.index()] {
+                cpu.press_button(*key);
+            } else {
+                cpu.release_button(*key);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod save_slots_tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_load_keeps_keypad() {
+        let dir = env::temp_dir().join(format!("chip8-slots-{}", std::process::id()));
+        let slots = SaveSlots::new(dir.clone(), String::from("rom"));
+        let mut cpu = Cpu::new();
+        cpu.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        cpu.press_button(Key::Num5);
+        slots.save(1, &cpu).unwrap();
+
+        cpu.release_button(Key::Num5);
+        cpu.press_button(Key::A);
+        slots.load(1, &mut cpu).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(!cpu.is_pressed(Key::Num5));
+        assert!(cpu.is_pressed(Key::A));
+        assert!(slots.load(2, &mut cpu).is_err());
+    }
+}
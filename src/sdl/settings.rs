@@ -0,0 +1,390 @@
+use crate::sdl::controller::ButtonBindings;
+use crate::sdl::input::{self, KeyBindings};
+use crate::sdl::paths;
+use crate::Config;
+use chip8::{Cpu, Key, Quirks};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sdl2::controller::Button;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+
+/// Settings shared by the config file and the command line. Fields that are
+/// not set leave the value from an earlier source alone
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub scale: Option<u32>,
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub border: Option<String>,
+    pub ips: Option<u64>,
+    pub quirks: Option<String>,
+    pub fullscreen: Option<bool>,
+    pub mute: Option<bool>,
+    pub volume: Option<f32>,
+    pub tone: Option<f32>,
+    pub rewind_seconds: Option<u32>,
+    /// Fraction of its brightness a cleared pixel keeps each frame, 0.0 to
+    /// turn the phosphor filter off
+    pub phosphor: Option<f32>,
+    /// Keypad keys, written as hex digits, mapped to the names of the
+    /// keyboard keys that press them. Keys not listed keep their default
+    /// bindings
+    pub keys: Option<BTreeMap<String, Vec<String>>>,
+    /// Keypad keys mapped to the names of the game controller buttons that
+    /// press them, e.g. "a", "dpup" or "leftshoulder"
+    pub buttons: Option<BTreeMap<String, Vec<String>>>,
+}
+
+/// Parses a colour written as `rrggbb` or `#rrggbb`
+pub fn parse_color(text: &str) -> Option<Color> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+fn color_setting(name: &str, text: &str) -> Result<Color, String> {
+    parse_color(text).ok_or_else(|| format!("{} expects a hex colour such as ff8000, got \"{}\"", name, text))
+}
+
+impl Settings {
+    /// Replaces these settings with the ones that are set in `other`
+    pub fn merge(&mut self, other: &Settings) {
+        fn take<T: Clone>(mine: &mut Option<T>, theirs: &Option<T>) {
+            if theirs.is_some() {
+                mine.clone_from(theirs);
+            }
+        }
+        take(&mut self.scale, &other.scale);
+        take(&mut self.fg, &other.fg);
+        take(&mut self.bg, &other.bg);
+        take(&mut self.border, &other.border);
+        take(&mut self.ips, &other.ips);
+        take(&mut self.quirks, &other.quirks);
+        take(&mut self.fullscreen, &other.fullscreen);
+        take(&mut self.mute, &other.mute);
+        take(&mut self.volume, &other.volume);
+        take(&mut self.tone, &other.tone);
+        take(&mut self.rewind_seconds, &other.rewind_seconds);
+        take(&mut self.phosphor, &other.phosphor);
+        take(&mut self.keys, &other.keys);
+        take(&mut self.buttons, &other.buttons);
+    }
+
+    /// Copies the settings that are set into `config`, checking their values
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        if let Some(scale) = self.scale {
+            if !(1..=100).contains(&scale) {
+                return Err(format!("scale must be between 1 and 100, got {}", scale));
+            }
+            config.scale = scale;
+        }
+        if let Some(fg) = &self.fg {
+            config.black = color_setting("fg", fg)?;
+        }
+        if let Some(bg) = &self.bg {
+            config.white = color_setting("bg", bg)?;
+        }
+        if let Some(border) = &self.border {
+            config.border = color_setting("border", border)?;
+        }
+        if let Some(ips) = self.ips {
+            // The speed is applied a whole frame at a time
+            let frame_rate = u64::from(Cpu::FRAME_RATE);
+            let rounded = ips.checked_add(frame_rate / 2)
+                .ok_or_else(|| format!("ips is too large, got {}", ips))?;
+            config.ipf = match u32::try_from(rounded / frame_rate) {
+                Ok(ipf) if ipf > 0 => ipf,
+                Ok(_) => return Err(format!("ips must be at least {}, got {}", frame_rate / 2, ips)),
+                Err(_) => return Err(format!("ips is too large, got {}", ips)),
+            };
+        }
+        if let Some(name) = &self.quirks {
+            config.quirks = Quirks::preset(name).ok_or_else(|| format!(
+                "quirks expects one of: {}, got \"{}\"", Quirks::PRESETS.join(", "), name))?;
+        }
+        if let Some(fullscreen) = self.fullscreen {
+            config.fullscreen = fullscreen;
+        }
+        if let Some(mute) = self.mute {
+            config.mute = mute;
+        }
+        if let Some(volume) = self.volume {
+            if !(0.0..=1.0).contains(&volume) {
+                return Err(format!("volume must be between 0.0 and 1.0, got {}", volume));
+            }
+            config.volume = volume;
+        }
+        if let Some(tone) = self.tone {
+            if !(20.0..=20_000.0).contains(&tone) {
+                return Err(format!("tone must be between 20 and 20000 Hz, got {}", tone));
+            }
+            config.tone = tone;
+        }
+        if let Some(seconds) = self.rewind_seconds {
+            // Each second of rewind keeps 60 snapshots of about 4KB each
+            if seconds > 300 {
+                return Err(format!("rewind_seconds must be between 0 and 300, got {}", seconds));
+            }
+            config.rewind_seconds = seconds;
+        }
+        if let Some(decay) = self.phosphor {
+            if !(0.0..=0.99).contains(&decay) {
+                return Err(format!("phosphor must be between 0.0 and 0.99, got {}", decay));
+            }
+            config.phosphor = decay;
+        }
+        if let Some(keys) = &self.keys {
+            config.keys = key_bindings(keys)?;
+        }
+        if let Some(buttons) = &self.buttons {
+            config.buttons = button_bindings(buttons)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a keypad key named in a `keys` or `buttons` table
+fn keypad_key(table: &str, name: &str) -> Result<Key, String> {
+    u8::from_str_radix(name, 16).ok().filter(|_| name.len() == 1).and_then(Key::from_index)
+        .ok_or_else(|| format!("{}: \"{}\" is not a keypad key, expected 0-9 or a-f", table, name))
+}
+
+/// Builds a layout from the default one and a `keys` table
+fn key_bindings(table: &BTreeMap<String, Vec<String>>) -> Result<KeyBindings, String> {
+    let mut bindings = KeyBindings::default();
+    let mut seen = HashMap::new();
+    for (name, keycode_names) in table.iter() {
+        let key = keypad_key("keys", name)?;
+        let mut keycodes = Vec::new();
+        for keycode_name in keycode_names.iter() {
+            let keycode = Keycode::from_name(keycode_name)
+                .ok_or_else(|| format!("keys: unknown key name \"{}\"", keycode_name))?;
+            if input::hotkey(keycode, Mod::NOMOD).is_some() {
+                return Err(format!("keys: \"{}\" is used by a hotkey", keycode_name));
+            }
+            if let Some(other) = seen.insert(keycode, name) {
+                return Err(format!("keys: \"{}\" is bound to both {} and {}", keycode_name, other, name));
+            }
+            keycodes.push(keycode);
+        }
+        bindings.bind(key, &keycodes);
+    }
+    Ok(bindings)
+}
+
+/// Builds a controller mapping from the default one and a `buttons` table
+fn button_bindings(table: &BTreeMap<String, Vec<String>>) -> Result<ButtonBindings, String> {
+    let mut bindings = ButtonBindings::default();
+    let mut seen = HashMap::new();
+    for (name, button_names) in table.iter() {
+        let key = keypad_key("buttons", name)?;
+        let mut buttons = Vec::new();
+        for button_name in button_names.iter() {
+            let button = Button::from_string(&button_name.to_ascii_lowercase())
+                .ok_or_else(|| format!("buttons: unknown button name \"{}\"", button_name))?;
+            if let Some(other) = seen.insert(button, name) {
+                return Err(format!("buttons: \"{}\" is bound to both {} and {}", button_name, other, name));
+            }
+            buttons.push(button);
+        }
+        bindings.bind(key, &buttons);
+    }
+    Ok(bindings)
+}
+
+/// The front end's config file, `config.toml` in the config directory:
+///
+/// ```toml
+/// [defaults]
+/// scale = 12
+/// fg = "ffffff"
+/// bg = "000000"
+/// phosphor = 0.6
+///
+/// # The default layout moved for AZERTY keyboards. Keyboard keys are named
+/// # as SDL names them and a keypad key can have several of them
+/// [defaults.keys]
+/// 4 = ["A"]
+/// 5 = ["Z"]
+/// 7 = ["Q"]
+/// a = ["W", "Space"]
+///
+/// # Game controller buttons, named as in SDL controller mappings
+/// [defaults.buttons]
+/// 5 = ["a", "rightshoulder"]
+///
+/// # Overrides for one ROM, keyed by the SHA-1 of the ROM file. A `keys` or
+/// # `buttons` table here replaces the one in [defaults]
+/// [rom.2aae6c35c94fcfb415dbe95f408b9ce91ee846ed]
+/// quirks = "vip"
+/// ips = 700
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub defaults: Settings,
+    #[serde(default)]
+    pub rom: HashMap<String, Settings>,
+}
+
+impl ConfigFile {
+    /// `config.toml` in the config directory, if there is one
+    pub fn default_path() -> Option<PathBuf> {
+        paths::config_dir().map(|dir| dir.join("config.toml"))
+    }
+
+    /// Reads the config file at `path`. A missing file is an empty config
+    pub fn load(path: &Path) -> Result<ConfigFile, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(ConfigFile::default()),
+            Err(err) => return Err(err.to_string()),
+        };
+        toml::from_str(&text).map_err(|err| err.to_string())
+    }
+
+    /// The defaults overridden by the section for the ROM with id `rom_id`
+    pub fn settings_for(&self, rom_id: &str) -> Settings {
+        let mut settings = self.defaults.clone();
+        let section = self.rom.iter().find(|(id, _)| id.eq_ignore_ascii_case(rom_id));
+        if let Some((_, overrides)) = section {
+            settings.merge(overrides);
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod settings_tests {
+    use super::*;
+
+    fn apply(text: &str) -> Result<Config, String> {
+        let settings: Settings = toml::from_str(text).unwrap();
+        let mut config = Config::default();
+        settings.apply(&mut config).map(|_| config)
+    }
+
+    fn apply_error(text: &str) -> String {
+        apply(text).err().expect("settings should be rejected")
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("ff8000"), Some(Color::RGB(0xff, 0x80, 0x00)));
+        assert_eq!(parse_color("#FF8000"), Some(Color::RGB(0xff, 0x80, 0x00)));
+        assert_eq!(parse_color("ff800"), None);
+        assert_eq!(parse_color("#ff80001"), None);
+        assert_eq!(parse_color("gg8000"), None);
+        assert_eq!(parse_color("+f8000"), None);
+    }
+
+    #[test]
+    fn test_apply() {
+        let config = apply("scale = 4\nfg = \"#ffffff\"\nips = 720\nquirks = \"schip\"\nrewind_seconds = 0").unwrap();
+        assert_eq!(config.scale, 4);
+        assert_eq!(config.black, Color::RGB(0xff, 0xff, 0xff));
+        assert_eq!(config.ipf, 12);
+        assert_eq!(config.quirks, Quirks::preset("schip").unwrap());
+        assert_eq!(config.rewind_seconds, 0);
+    }
+
+    #[test]
+    fn test_unknown_setting() {
+        assert!(toml::from_str::<Settings>("colour = \"ff8000\"").is_err());
+    }
+
+    #[test]
+    fn test_range_checks() {
+        assert!(apply_error("scale = 0").starts_with("scale must be between 1 and 100"));
+        assert!(apply_error("scale = 101").starts_with("scale must be between 1 and 100"));
+        assert!(apply_error("fg = \"red\"").starts_with("fg expects a hex colour"));
+        assert!(apply_error("volume = 1.5").starts_with("volume must be between"));
+        assert!(apply_error("tone = 10.0").starts_with("tone must be between"));
+        assert!(apply_error("phosphor = 1.0").starts_with("phosphor must be between"));
+        assert!(apply_error("rewind_seconds = 301").starts_with("rewind_seconds must be between"));
+        assert!(apply_error("ips = 29").starts_with("ips must be at least 30"));
+        assert!(apply_error("ips = 9000000000000").starts_with("ips is too large"));
+        assert!(apply_error("quirks = \"eti\"").starts_with("quirks expects one of"));
+        assert!(apply("rewind_seconds = 300").is_ok());
+    }
+
+    #[test]
+    fn test_ips_overflow() {
+        let settings = Settings { ips: Some(u64::MAX), ..Settings::default() };
+        let err = settings.apply(&mut Config::default()).err().unwrap();
+        assert!(err.starts_with("ips is too large"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut settings: Settings = toml::from_str("scale = 12\nfg = \"ffffff\"").unwrap();
+        let overrides: Settings = toml::from_str("scale = 5\nmute = true").unwrap();
+        settings.merge(&overrides);
+        assert_eq!(settings.scale, Some(5));
+        assert_eq!(settings.fg.as_deref(), Some("ffffff"));
+        assert_eq!(settings.mute, Some(true));
+        assert_eq!(settings.volume, None);
+    }
+
+    #[test]
+    fn test_settings_for() {
+        let file: ConfigFile = toml::from_str("
+            [defaults]
+            scale = 12
+            ips = 600
+
+            [rom.ABCDEF]
+            ips = 900
+        ").unwrap();
+        let settings = file.settings_for("abcdef");
+        assert_eq!(settings.scale, Some(12));
+        assert_eq!(settings.ips, Some(900));
+        assert_eq!(file.settings_for("012345").ips, Some(600));
+    }
+
+    #[test]
+    fn test_key_bindings() {
+        // AZERTY: A takes over keypad 4 from Q, leaving 7 without a key
+        let config = apply("[keys]\n4 = [\"A\"]\nb = [\"C\", \"Space\"]").unwrap();
+        assert_eq!(config.keys.key(Keycode::A), Some(Key::Num4));
+        assert_eq!(config.keys.key(Keycode::Q), None);
+        assert_eq!(config.keys.key(Keycode::C), Some(Key::B));
+        assert_eq!(config.keys.key(Keycode::Space), Some(Key::B));
+        assert_eq!(config.keys.key(Keycode::W), Some(Key::Num5));
+    }
+
+    #[test]
+    fn test_key_binding_errors() {
+        assert!(apply_error("[keys]\n4 = [\"A\"]\n7 = [\"A\"]").contains("bound to both 4 and 7"));
+        assert!(apply_error("[keys]\n4 = [\"M\"]").contains("used by a hotkey"));
+        assert!(apply_error("[keys]\n4 = [\"NoSuchKey\"]").contains("unknown key name"));
+        assert!(apply_error("[keys]\ng = [\"A\"]").contains("is not a keypad key"));
+        assert!(apply_error("[keys]\n10 = [\"A\"]").contains("is not a keypad key"));
+    }
+
+    #[test]
+    fn test_button_bindings() {
+        let config = apply("[buttons]\n5 = [\"A\", \"rightshoulder\"]\nc = [\"b\"]").unwrap();
+        assert_eq!(config.buttons.key(Button::A), Some(Key::Num5));
+        assert_eq!(config.buttons.key(Button::RightShoulder), Some(Key::Num5));
+        assert_eq!(config.buttons.key(Button::B), Some(Key::C));
+        assert_eq!(config.buttons.key(Button::DPadUp), Some(Key::Num2));
+    }
+
+    #[test]
+    fn test_button_binding_errors() {
+        assert!(apply_error("[buttons]\n5 = [\"a\"]\n6 = [\"a\"]").contains("bound to both 5 and 6"));
+        assert!(apply_error("[buttons]\n5 = [\"trigger\"]").contains("unknown button name"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::ops::Deref;
+
+/// Why a push or pop failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackError {
+    Overflow,
+    Underflow,
+}
+
+/// Fixed-depth call stack of return addresses. Dereferences to a slice with
+/// the innermost return address last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stack {
+    entries: Vec<u16>,
+    depth: usize,
+}
+
+impl Stack {
+    /// 16 levels, as on the COSMAC VIP and SUPER-CHIP
+    pub const DEFAULT_DEPTH: usize = 16;
+
+    pub fn new(depth: usize) -> Stack {
+        Stack { entries: Vec::with_capacity(depth), depth }
+    }
+
+    /// Maximum number of nested subroutine calls
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn push(&mut self, address: u16) -> Result<(), StackError> {
+        if self.entries.len() >= self.depth {
+            return Err(StackError::Overflow);
+        }
+        self.entries.push(address);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<u16, StackError> {
+        self.entries.pop().ok_or(StackError::Underflow)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Stack::new(Stack::DEFAULT_DEPTH)
+    }
+}
+
+impl Deref for Stack {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod stack_tests {
+    use super::*;
+
+    #[test]
+    fn test_push_pop() {
+        let mut stack = Stack::new(2);
+        assert_eq!(stack.pop(), Err(StackError::Underflow));
+        stack.push(0x200).unwrap();
+        stack.push(0x300).unwrap();
+        assert_eq!(stack.push(0x400), Err(StackError::Overflow));
+        assert_eq!(&stack[..], &[0x200, 0x300]);
+        assert_eq!(stack.pop(), Ok(0x300));
+        assert_eq!(stack.pop(), Ok(0x200));
+        assert!(stack.is_empty());
+    }
+}
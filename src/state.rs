@@ -0,0 +1,188 @@
+use crate::error::Chip8Error;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A copy of everything that changes while a program runs, taken with
+/// `Cpu::snapshot` and put back with `Cpu::restore`. The platform, quirks and
+/// speed are configuration and are not part of it
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineState {
+    pub memory: Vec<u8>,
+    /// The framebuffer row by row, one byte per pixel
+    pub graphics: Vec<u8>,
+    pub v: [u8; 16],
+    pub index: u16,
+    pub pc: u16,
+    /// Return addresses, innermost last
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keypad: [bool; 16],
+    /// True if a 60Hz tick happened since the last sprite was drawn
+    pub vblank: bool,
+    /// State of the random number generator, if it can be saved
+    pub rng: Option<u64>,
+}
+
+impl MachineState {
+    /// Identifies files written by `to_bytes`
+    pub const MAGIC: [u8; 4] = *b"C8ST";
+    /// Bumped whenever the layout written by `to_bytes` changes
+    pub const FORMAT_VERSION: u16 = 1;
+
+    /// Encodes the state in a compact binary format, prefixed by `MAGIC` and
+    /// `FORMAT_VERSION`. All integers are little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + self.graphics.len() + 64);
+        out.extend_from_slice(&MachineState::MAGIC);
+        out.extend_from_slice(&MachineState::FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&(self.graphics.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.graphics);
+        out.extend_from_slice(&self.v);
+        out.extend_from_slice(&self.index.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&(self.stack.len() as u32).to_le_bytes());
+        for address in self.stack.iter() {
+            out.extend_from_slice(&address.to_le_bytes());
+        }
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        let keypad = self.keypad.iter().rev().fold(0u16, |bits, held| bits << 1 | u16::from(*held));
+        out.extend_from_slice(&keypad.to_le_bytes());
+        out.push(u8::from(self.vblank));
+        match self.rng {
+            Some(rng) => {
+                out.push(1);
+                out.extend_from_slice(&rng.to_le_bytes());
+            },
+            None => out.push(0),
+        }
+        out
+    }
+
+    /// Decodes a state written by `to_bytes`. Fails on a missing header, a
+    /// different format version or truncated data
+    pub fn from_bytes(bytes: &[u8]) -> Result<MachineState, Chip8Error> {
+        let mut input = Input { bytes };
+        if input.take(4)? != MachineState::MAGIC {
+            return Err(Chip8Error::InvalidState(String::from("not a CHIP-8 machine state")));
+        }
+        let version = input.u16()?;
+        if version != MachineState::FORMAT_VERSION {
+            return Err(Chip8Error::InvalidState(format!(
+                "format version {} is not supported, expected {}", version, MachineState::FORMAT_VERSION)));
+        }
+
+        let len = input.u32()? as usize;
+        let memory = input.take(len)?.to_vec();
+        let len = input.u32()? as usize;
+        let graphics = input.take(len)?.to_vec();
+        let mut v = [0; 16];
+        v.copy_from_slice(input.take(16)?);
+        let index = input.u16()?;
+        let pc = input.u16()?;
+        let len = input.u32()? as usize;
+        let stack = (0..len).map(|_| input.u16()).collect::<Result<Vec<u16>, Chip8Error>>()?;
+        let delay_timer = input.u8()?;
+        let sound_timer = input.u8()?;
+        let bits = input.u16()?;
+        let mut keypad = [false; 16];
+        for (i, held) in keypad.iter_mut().enumerate() {
+            *held = bits & (1 << i) != 0;
+        }
+        let vblank = input.u8()? != 0;
+        let rng = match input.u8()? {
+            0 => None,
+            _ => Some(u64::from_le_bytes(input.array()?)),
+        };
+        if !input.bytes.is_empty() {
+            return Err(Chip8Error::InvalidState(String::from("trailing data after machine state")));
+        }
+
+        Ok(MachineState {
+            memory, graphics, v, index, pc, stack, delay_timer, sound_timer, keypad, vblank, rng,
+        })
+    }
+}
+
+/// Reads the fields of an encoded state in order
+struct Input<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.bytes.len() < len {
+            return Err(Chip8Error::InvalidState(String::from("machine state is truncated")));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Chip8Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Chip8Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::keypad::Key;
+
+    fn sample_state() -> MachineState {
+        let mut cpu = Cpu::new();
+        cpu.set_seed(3);
+        cpu.load_rom_bytes(&[0x22, 0x04, 0x00, 0x00, 0xA0, 0x0A, 0xD0, 0x15]).unwrap();
+        cpu.advance_state().unwrap();
+        cpu.advance_state().unwrap();
+        cpu.advance_state().unwrap();
+        cpu.press_button(Key::Num1);
+        cpu.press_button(Key::F);
+        cpu.snapshot()
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let state = sample_state();
+        let bytes = state.to_bytes();
+        assert_eq!(&bytes[0..6], b"C8ST\x01\x00");
+        assert_eq!(MachineState::from_bytes(&bytes).unwrap(), state);
+    }
+
+    #[test]
+    fn test_bytes_rejects_bad_input() {
+        let bytes = sample_state().to_bytes();
+        assert!(matches!(MachineState::from_bytes(b"PK\x03\x04 zip"), Err(Chip8Error::InvalidState(_))));
+
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        match MachineState::from_bytes(&newer) {
+            Err(Chip8Error::InvalidState(reason)) => assert!(reason.contains("version 2")),
+            other => panic!("expected a version error, got {:?}", other),
+        }
+
+        assert!(MachineState::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut longer = bytes;
+        longer.push(0);
+        assert!(MachineState::from_bytes(&longer).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Runs the headless binary end to end and checks what it writes to stdout
+
+use std::env;
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_json_output_is_clean() {
+    // 00E0 clears the screen, 1200 jumps back to it
+    let rom = env::temp_dir().join(format!("chip8-cls-{}.ch8", std::process::id()));
+    fs::write(&rom, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .arg(&rom)
+        .args(["--frames", "1", "--json"])
+        .output()
+        .unwrap();
+    fs::remove_file(&rom).unwrap();
+
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["frames"], 1);
+    assert_eq!(report["cycles"], 10);
+    assert!(report["error"].is_null());
+}
+
+#[test]
+fn test_zero_ipf_is_rejected() {
+    let rom = env::temp_dir().join(format!("chip8-ipf-{}.ch8", std::process::id()));
+    fs::write(&rom, [0x12, 0x00]).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .arg(&rom)
+        .args(["--cycles", "5", "--ipf", "0"])
+        .output()
+        .unwrap();
+    fs::remove_file(&rom).unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--ipf must be at least 1"));
+}